@@ -0,0 +1,109 @@
+//! Times building, updating and drawing a 4K `Canvas`, the way the game does for every slide and frame,
+//! against the per-pixel conversion every redraw used to do.
+//!
+//! The benchmark writes a small story to a temporary folder and reads it from there, so it needs
+//! the portable feature. Run with `cargo bench --bench present --features portable`.
+
+use std::{env, fs, hint::black_box, time::{Duration, Instant}};
+
+use image::{DynamicImage, Pixel, Rgb, Rgba, RgbaImage};
+
+use ferrous_tale::{cache::IMAGE_CACHE, draw::Canvas, game::{Game, SaveFile}, settings::Settings};
+
+
+const WIDTH: u32 = 3840;
+const HEIGHT: u32 = 2160;
+const ITERATIONS: u32 = 20;
+
+const SLIDE: &str = "\
+background_path: bg.png
+nonclickables:
+  - image_path: element.png
+    position: {x: 0.25, y: 0.4}
+    fade_in: 1000
+clickables:
+  - slide_path: /
+    image_path: element.png
+    position: {x: 0.5, y: 0.5}
+";
+
+
+fn main() {
+    let directory = env::temp_dir().join("ferrous-tale-bench");
+    fs::create_dir_all(directory.join("story")).unwrap();
+    fs::write(directory.join("story/slide.yaml"), SLIDE).unwrap();
+    RgbaImage::from_fn(WIDTH, HEIGHT, |x, y| Rgba([x as u8, y as u8, (x ^ y) as u8, 255]))
+        .save(directory.join("story/bg.png"))
+        .unwrap();
+    RgbaImage::from_fn(256, 256, |x, y| Rgba([255, x as u8, y as u8, 160]))
+        .save(directory.join("story/element.png"))
+        .unwrap();
+    let previous_directory = env::current_dir().unwrap();
+    env::set_current_dir(&directory).unwrap();
+
+    let game: Game = SaveFile::default().try_into().unwrap();
+    let settings = Settings::default();
+    let mut buffer = vec![0u32; (WIDTH * HEIGHT) as usize];
+
+    let cold_build = time(|| {
+        IMAGE_CACHE.lock().unwrap().clear();
+        black_box(Canvas::build(&game, &settings));
+    });
+    report("build, images decoded", cold_build);
+
+    let warm_build = time(|| {
+        black_box(Canvas::build(&game, &settings));
+    });
+    report("build, images cached", warm_build);
+
+    let mut canvas = Canvas::build(&game, &settings);
+    let full_draw = time(|| {
+        black_box(canvas.draw_to_buffer(&mut buffer, 0, WIDTH, HEIGHT));
+    });
+    report("draw, full redraw", full_draw);
+
+    // the nonclickable fades in over many seconds, so every update redraws its 256x256 square
+    let mut elapsed = Duration::ZERO;
+    let fading_frame = time(|| {
+        elapsed += Duration::from_millis(16);
+        assert!(canvas.seek(elapsed));
+        black_box(canvas.draw_to_buffer(&mut buffer, 1, WIDTH, HEIGHT));
+    });
+    report("update and draw while fading", fading_frame);
+
+    let composited = DynamicImage::ImageRgba8(RgbaImage::from_fn(WIDTH, HEIGHT, |x, y| Rgba([x as u8, y as u8, 0, 255])));
+    let old_draw = time(|| old_draw_to_buffer(&composited, &mut buffer, WIDTH, HEIGHT));
+    report("old per-pixel conversion", old_draw);
+    println!();
+    println!("full redraw is {:.1}x faster than before", old_draw.as_secs_f64() / full_draw.as_secs_f64());
+    println!("a fading frame is {:.1}x faster than before", old_draw.as_secs_f64() / fading_frame.as_secs_f64());
+
+    black_box(&buffer);
+    env::set_current_dir(previous_directory).unwrap();
+    fs::remove_dir_all(directory).unwrap();
+}
+
+/// How every redraw worked before the canvas kept a framebuffer, kept here as the baseline
+fn old_draw_to_buffer(image: &DynamicImage, buffer: &mut [u32], width: u32, height: u32) {
+    let image = image.clone().into_rgb8();
+    for index in 0..(width * height) {
+        let y = index / width;
+        let x = index % width;
+        let pixel = image.get_pixel_checked(x, y).unwrap_or(&Rgb::<u8>::from([0, 0, 0])).to_rgb();
+        let channels = pixel.channels();
+        buffer[index as usize] = (channels[0] as u32) << 16 | (channels[1] as u32) << 8 | channels[2] as u32;
+    }
+}
+
+fn time(mut f: impl FnMut()) -> Duration {
+    f();
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    start.elapsed() / ITERATIONS
+}
+
+fn report(name: &str, duration: Duration) {
+    println!("{name:<32} {:>10.3} ms", duration.as_secs_f64() * 1000.0);
+}
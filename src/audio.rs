@@ -0,0 +1,286 @@
+use std::{path::{Path, PathBuf}, time::{Duration, Instant}};
+
+use crate::{draw::FRAME_TIME, error::Error, filesystem::{canonical_join, prefix_path, read_asset}, report::report_error};
+
+
+// Written by soweli Luna
+
+/// How long the old track takes to fade out while the new one fades in
+const MUSIC_CROSSFADE: Duration = Duration::from_millis(1000);
+
+
+/// Something that can actually make noise, so the engine can run without a sound device
+pub trait AudioBackend {
+    /// Starts looping an encoded OGG or WAV track, silent until its volume is raised
+    fn start_music(&mut self, data: Vec<u8>) -> Result<TrackId, Error>;
+    fn set_music_volume(&mut self, track: TrackId, volume: f32);
+    fn stop_music(&mut self, track: TrackId);
+    /// Plays an encoded OGG or WAV sound once
+    fn play_sound(&mut self, data: Vec<u8>) -> Result<(), Error>;
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct TrackId(u64);
+
+
+/// Plays slide music and clickable sounds, crossfading when the music changes
+pub struct Audio {
+    backend: Box<dyn AudioBackend>,
+    /// The resolved path of the music that should be playing
+    music_path: Option<PathBuf>,
+    fading_in: Option<TrackId>,
+    fading_out: Vec<TrackId>,
+    fade_start: Instant,
+}
+impl Default for Audio {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl Audio {
+    pub fn new() -> Self {
+        Self::with_backend(default_backend())
+    }
+
+    fn with_backend(backend: Box<dyn AudioBackend>) -> Self {
+        Self {
+            backend,
+            music_path: None,
+            fading_in: None,
+            fading_out: Vec::new(),
+            fade_start: Instant::now(),
+        }
+    }
+
+    /// Switches to the music of the slide at `location`, carrying on if it's the track already playing
+    pub fn set_music(&mut self, location: &Path, music: Option<&PathBuf>) {
+        let music_path = music.map(|music| prefix_path(&canonical_join(location, music)));
+        self.switch_music(music_path, read_asset);
+    }
+
+    /// Crossfades to the track at `music_path`, read with `read`
+    fn switch_music(&mut self, music_path: Option<PathBuf>, read: impl FnOnce(&Path) -> Result<Vec<u8>, Error>) {
+        if music_path == self.music_path {
+            return
+        }
+
+        self.fading_out.extend(self.fading_in.take());
+        self.fade_start = Instant::now();
+        self.music_path = music_path;
+
+        log::debug!("switching music to {:?}", self.music_path);
+        if let Some(music_path) = &self.music_path {
+            match read(music_path).and_then(|data| self.backend.start_music(data)) {
+                Ok(track) => self.fading_in = Some(track),
+                Err(e) => report_error(format!("could not play music {music_path:?}: {e}")),
+            }
+        }
+        self.update();
+    }
+
+    pub fn play_sound(&mut self, location: &Path, sound: &Path) {
+        let sound_path = prefix_path(&canonical_join(location, sound));
+        log::debug!("playing sound {sound_path:?}");
+        if let Err(e) = read_asset(&sound_path).and_then(|data| self.backend.play_sound(data)) {
+            report_error(format!("could not play sound {sound_path:?}: {e}"));
+        }
+    }
+
+    /// Advances the crossfade between tracks
+    pub fn update(&mut self) {
+        let progress = (self.fade_start.elapsed().as_secs_f32() / MUSIC_CROSSFADE.as_secs_f32()).min(1.0);
+        if let Some(track) = self.fading_in {
+            self.backend.set_music_volume(track, progress);
+        }
+        for track in &self.fading_out {
+            self.backend.set_music_volume(*track, 1.0 - progress);
+        }
+        if progress >= 1.0 {
+            for track in self.fading_out.drain(..) {
+                self.backend.stop_music(track);
+            }
+        }
+    }
+
+    /// When `update` next needs to be called, if a crossfade is in progress
+    pub fn next_update(&self) -> Option<Instant> {
+        let fading = self.fade_start.elapsed() < MUSIC_CROSSFADE;
+        fading.then(|| Instant::now() + FRAME_TIME)
+    }
+}
+
+
+/// Used when the engine is built without the `audio` feature, or when there's no sound device
+#[derive(Default)]
+pub struct NullAudio {
+    next_track: u64,
+}
+impl AudioBackend for NullAudio {
+    fn start_music(&mut self, _data: Vec<u8>) -> Result<TrackId, Error> {
+        self.next_track += 1;
+        Ok(TrackId(self.next_track))
+    }
+
+    fn set_music_volume(&mut self, _track: TrackId, _volume: f32) {}
+
+    fn stop_music(&mut self, _track: TrackId) {}
+
+    fn play_sound(&mut self, _data: Vec<u8>) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+
+#[cfg(feature = "audio")]
+fn default_backend() -> Box<dyn AudioBackend> {
+    match rodio_backend::RodioAudio::new() {
+        Ok(backend) => Box::new(backend),
+        Err(e) => {
+            report_error(format!("could not open audio device, continuing without sound: {e}"));
+            Box::<NullAudio>::default()
+        }
+    }
+}
+
+#[cfg(not(feature = "audio"))]
+fn default_backend() -> Box<dyn AudioBackend> {
+    Box::<NullAudio>::default()
+}
+
+
+#[cfg(feature = "audio")]
+mod rodio_backend {
+    use std::{collections::HashMap, io::Cursor};
+
+    use rodio::{Decoder, OutputStream, OutputStreamHandle, Sink};
+
+    use crate::error::Error;
+
+    use super::{AudioBackend, TrackId};
+
+    pub struct RodioAudio {
+        // dropping the stream stops all sound, so it's kept alongside its handle
+        _stream: OutputStream,
+        handle: OutputStreamHandle,
+        tracks: HashMap<TrackId, Sink>,
+        next_track: u64,
+    }
+    impl RodioAudio {
+        pub fn new() -> Result<Self, Error> {
+            let (stream, handle) = OutputStream::try_default().map_err(|e| Error::Audio(e.to_string()))?;
+            Ok(Self {
+                _stream: stream,
+                handle,
+                tracks: HashMap::new(),
+                next_track: 0,
+            })
+        }
+    }
+    impl AudioBackend for RodioAudio {
+        fn start_music(&mut self, data: Vec<u8>) -> Result<TrackId, Error> {
+            let source = Decoder::new_looped(Cursor::new(data)).map_err(|e| Error::Audio(e.to_string()))?;
+            let sink = Sink::try_new(&self.handle).map_err(|e| Error::Audio(e.to_string()))?;
+            sink.set_volume(0.0);
+            sink.append(source);
+
+            self.next_track += 1;
+            let track = TrackId(self.next_track);
+            self.tracks.insert(track, sink);
+            Ok(track)
+        }
+
+        fn set_music_volume(&mut self, track: TrackId, volume: f32) {
+            if let Some(sink) = self.tracks.get(&track) {
+                sink.set_volume(volume);
+            }
+        }
+
+        fn stop_music(&mut self, track: TrackId) {
+            if let Some(sink) = self.tracks.remove(&track) {
+                sink.stop();
+            }
+        }
+
+        fn play_sound(&mut self, data: Vec<u8>) -> Result<(), Error> {
+            let source = Decoder::new(Cursor::new(data)).map_err(|e| Error::Audio(e.to_string()))?;
+            let sink = Sink::try_new(&self.handle).map_err(|e| Error::Audio(e.to_string()))?;
+            sink.append(source);
+            sink.detach();
+            Ok(())
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_nothing(_: &Path) -> Result<Vec<u8>, Error> {
+        Ok(Vec::new())
+    }
+
+    fn silent_audio() -> Audio {
+        Audio::with_backend(Box::<NullAudio>::default())
+    }
+
+    /// Pretends the crossfade started long enough ago to be over
+    fn finish_crossfade(audio: &mut Audio) {
+        audio.fade_start -= MUSIC_CROSSFADE;
+        audio.update();
+    }
+
+    #[test]
+    fn music_starts_fading_in() {
+        let mut audio = silent_audio();
+        audio.switch_music(Some("story/theme.ogg".into()), read_nothing);
+        assert_eq!(audio.fading_in, Some(TrackId(1)));
+        assert!(audio.fading_out.is_empty());
+        assert!(audio.next_update().is_some());
+
+        finish_crossfade(&mut audio);
+        assert_eq!(audio.fading_in, Some(TrackId(1)));
+        assert!(audio.next_update().is_none());
+    }
+
+    #[test]
+    fn changing_music_crossfades_and_stops_the_old_track() {
+        let mut audio = silent_audio();
+        audio.switch_music(Some("story/theme.ogg".into()), read_nothing);
+        finish_crossfade(&mut audio);
+
+        audio.switch_music(Some("story/cave/drips.ogg".into()), read_nothing);
+        assert_eq!(audio.fading_in, Some(TrackId(2)));
+        assert_eq!(audio.fading_out, vec![TrackId(1)]);
+
+        finish_crossfade(&mut audio);
+        assert!(audio.fading_out.is_empty());
+    }
+
+    #[test]
+    fn same_track_keeps_playing() {
+        let mut audio = silent_audio();
+        audio.switch_music(Some("story/theme.ogg".into()), read_nothing);
+        audio.switch_music(Some("story/theme.ogg".into()), |_| panic!("the same track was read again"));
+        assert_eq!(audio.fading_in, Some(TrackId(1)));
+        assert!(audio.fading_out.is_empty());
+    }
+
+    #[test]
+    fn slide_without_music_fades_out() {
+        let mut audio = silent_audio();
+        audio.switch_music(Some("story/theme.ogg".into()), read_nothing);
+        audio.switch_music(None, read_nothing);
+        assert_eq!(audio.fading_in, None);
+        assert_eq!(audio.fading_out, vec![TrackId(1)]);
+    }
+
+    #[test]
+    fn same_music_path_resolves_the_same_from_any_slide() {
+        let mut audio = silent_audio();
+        audio.set_music(Path::new("/forest"), Some(&"/theme.ogg".into()));
+        let path = audio.music_path.clone();
+        audio.set_music(Path::new("/cave"), Some(&"../theme.ogg".into()));
+        assert_eq!(audio.music_path, path);
+    }
+}
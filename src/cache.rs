@@ -0,0 +1,147 @@
+use std::{collections::HashMap, path::{Path, PathBuf}, sync::{Arc, LazyLock, Mutex}};
+
+use crate::filesystem::Frame;
+
+
+
+/// Decoded images are kept around until they take up more than this many bytes
+const IMAGE_CACHE_BUDGET: usize = 256 * 1024 * 1024;
+
+pub static IMAGE_CACHE: LazyLock<Mutex<ImageCache>> = LazyLock::new(|| {
+    Mutex::new(ImageCache::new(IMAGE_CACHE_BUDGET))
+});
+
+
+/// Least recently used cache of decoded images, with every frame of animated ones, 
+/// keyed by their path from `prefix_path` with any `.` and `..` worked out
+pub struct ImageCache {
+    entries: HashMap<PathBuf, CacheEntry>,
+    budget: usize,
+    used: usize,
+    clock: u64,
+}
+struct CacheEntry {
+    frames: Arc<Vec<Frame>>,
+    size: usize,
+    last_used: u64,
+}
+impl ImageCache {
+    pub fn new(budget: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            budget,
+            used: 0,
+            clock: 0,
+        }
+    }
+
+    pub fn get(&mut self, path: &Path) -> Option<Arc<Vec<Frame>>> {
+        self.clock += 1;
+        let entry = self.entries.get_mut(path)?;
+        entry.last_used = self.clock;
+        Some(entry.frames.clone())
+    }
+
+    /// Whether `path` is cached, without counting as a use
+    pub fn contains(&self, path: &Path) -> bool {
+        self.entries.contains_key(path)
+    }
+
+    pub fn insert(&mut self, path: PathBuf, frames: Arc<Vec<Frame>>) {
+        let size = frames.iter().map(|frame| frame.image.as_raw().len()).sum();
+        if size > self.budget {
+            return
+        }
+        self.remove(&path);
+        while self.used + size > self.budget {
+            self.evict_oldest();
+        }
+
+        self.clock += 1;
+        self.used += size;
+        self.entries.insert(path, CacheEntry {
+            frames,
+            size,
+            last_used: self.clock,
+        });
+    }
+
+    pub fn remove(&mut self, path: &Path) {
+        if let Some(entry) = self.entries.remove(path) {
+            self.used -= entry.size;
+        }
+    }
+
+    /// Removes every image whose path `matches`
+    pub fn remove_matching(&mut self, mut matches: impl FnMut(&Path) -> bool) {
+        let paths: Vec<PathBuf> = self.entries.keys().filter(|path| matches(path)).cloned().collect();
+        for path in paths {
+            self.remove(&path);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.used = 0;
+    }
+
+    fn evict_oldest(&mut self) {
+        let oldest = self.entries
+            .iter()
+            .min_by_key(|(_, entry)| entry.last_used)
+            .map(|(path, _)| path.clone());
+        if let Some(path) = oldest {
+            self.remove(&path);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use image::RgbaImage;
+
+    use super::*;
+
+    /// A single frame image taking up `pixels * 4` bytes
+    fn frames(pixels: u32) -> Arc<Vec<Frame>> {
+        Arc::new(vec![Frame { image: RgbaImage::new(pixels, 1), delay: Duration::ZERO }])
+    }
+
+    #[test]
+    fn least_recently_used_image_is_evicted_first() {
+        let mut cache = ImageCache::new(250);
+        cache.insert("a.png".into(), frames(25));
+        cache.insert("b.png".into(), frames(25));
+        assert!(cache.get(Path::new("a.png")).is_some());
+        cache.insert("c.png".into(), frames(25));
+
+        assert!(cache.contains(Path::new("a.png")));
+        assert!(!cache.contains(Path::new("b.png")));
+        assert!(cache.contains(Path::new("c.png")));
+        assert_eq!(cache.used, 200);
+    }
+
+    #[test]
+    fn images_bigger_than_the_budget_are_not_cached() {
+        let mut cache = ImageCache::new(100);
+        cache.insert("small.png".into(), frames(10));
+        cache.insert("huge.png".into(), frames(26));
+        assert!(!cache.contains(Path::new("huge.png")));
+        assert!(cache.contains(Path::new("small.png")));
+        assert_eq!(cache.used, 40);
+    }
+
+    #[test]
+    fn replacing_and_removing_keep_the_size_right() {
+        let mut cache = ImageCache::new(1000);
+        cache.insert("a.png".into(), frames(10));
+        cache.insert("a.png".into(), frames(20));
+        assert_eq!(cache.used, 80);
+        cache.remove_matching(|path| path == Path::new("a.png"));
+        assert_eq!(cache.used, 0);
+        assert!(!cache.contains(Path::new("a.png")));
+    }
+}
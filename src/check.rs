@@ -0,0 +1,571 @@
+use std::{collections::{BTreeSet, HashMap, HashSet, VecDeque}, fmt, path::{Path, PathBuf}};
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::{
+    draw::{hotspot_hitbox, position_asset}, 
+    error::Error, 
+    filesystem::{self, canonical_join, decode_image, prefix_path, slide_file, validate_path, Slide}, 
+    game::{Clickable, Coords, SpriteSheet}, 
+    graph::{EdgeKind, StoryGraph},
+    manifest::{Manifest, MANIFEST_FILENAME},
+    settings::Bindings,
+};
+
+
+// Written by soweli Luna
+
+/// Stories with many independent keys can have a huge number of states, so the search gives up here
+const MAX_STATES: usize = 1_000_000;
+
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default)]
+pub enum CheckFormat {
+    /// One line per issue, like a compiler
+    #[default]
+    Human,
+    /// A JSON array of diagnostics, for CI and editor tooling
+    Json,
+}
+
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// The story is broken, the player will see fallbacks or get stuck
+    Error,
+    /// Probably a mistake, but the story still plays
+    Warning,
+}
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Error => write!(f, "error"),
+            Self::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+
+/// One issue found in the story
+#[derive(Serialize, Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    /// The file the issue is in, as the story resolves it. That's relative to the working directory in
+    /// portable builds, a path inside the pack when reading one, and otherwise a path inside the
+    /// `assets/` folder the executable was built from.
+    pub file: Option<PathBuf>,
+    /// 1-based, only known for YAML syntax and type errors
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+}
+impl Diagnostic {
+    fn new(severity: Severity, file: Option<PathBuf>, message: String) -> Self {
+        Self { severity, message, file, line: None, column: None }
+    }
+
+    /// Turns a loading error into a diagnostic, pointing at the offending line if it's a YAML error
+    fn from_error(file: PathBuf, error: &Error) -> Self {
+        match error {
+            Error::Yaml { source, .. } => {
+                let location = source.location();
+                let mut message = source.to_string();
+                // the location is printed in its own columns
+                if let Some(location) = &location {
+                    message = message.replacen(&format!(" at line {} column {}", location.line(), location.column()), "", 1);
+                }
+                Self {
+                    severity: Severity::Error,
+                    message,
+                    file: Some(file),
+                    line: location.as_ref().map(|location| location.line()),
+                    column: location.as_ref().map(|location| location.column()),
+                }
+            }
+            error => Self::new(Severity::Error, Some(file), error.to_string()),
+        }
+    }
+}
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.severity)?;
+        if let Some(file) = &self.file {
+            write!(f, "{}", file.display())?;
+            if let (Some(line), Some(column)) = (self.line, self.column) {
+                write!(f, ":{line}:{column}")?;
+            }
+            write!(f, ": ")?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+
+/// Recursively searches the story tree from `start` for issues
+pub fn check_story(start: PathBuf) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let graph = StoryGraph::build(start.clone());
+
+    let manifest_file = prefix_path(Path::new(MANIFEST_FILENAME));
+    match Manifest::read() {
+        Ok((_, unknown_fields)) => diagnostics.extend(unknown_field_errors(&manifest_file, unknown_fields)),
+        Err(Error::Missing { .. }) => (),
+        Err(e) => diagnostics.push(Diagnostic::from_error(manifest_file, &e)),
+    }
+
+    for location in &graph.broken {
+        if let Err(e) = Slide::read_yaml(location) {
+            diagnostics.push(Diagnostic::from_error(slide_file(location), &e));
+        }
+    }
+
+    for location in &graph.slides {
+        let Ok((slide, unknown_fields)) = Slide::read_yaml_checked(location) else { continue };
+        diagnostics.extend(unknown_field_errors(&slide_file(location), unknown_fields));
+        let mut image_sizes = HashMap::new();
+        for (image_path, _) in slide.images() {
+            match decode_image(&prefix_path(&canonical_join(location, image_path))) {
+                Ok(image) => {
+                    image_sizes.insert(image_path, Coords { x: image.width() as i32, y: image.height() as i32 });
+                }
+                Err(e) => diagnostics.push(Diagnostic::from_error(slide_file(location), &e)),
+            }
+        }
+        check_placement(location, &slide, &image_sizes, &mut diagnostics);
+        check_sprite_sheets(location, &slide, &image_sizes, &mut diagnostics);
+        check_hotkeys(location, &slide, &mut diagnostics);
+        for full_path in referenced_paths(location, &slide) {
+            if let Err(e) = validate_path(&full_path) {
+                diagnostics.push(Diagnostic::from_error(slide_file(location), &e));
+            }
+        }
+    }
+
+    let mut dir_slides_found = HashSet::new();
+    if let Err(e) = filesystem::recursive_check_dir(prefix_path(&start), &mut dir_slides_found) {
+        diagnostics.push(Diagnostic::new(Severity::Error, None, e.to_string()));
+    }
+    let yaml_slides_visited: HashSet<PathBuf> = graph.slides.iter().chain(&graph.broken).map(|location| slide_file(location)).collect();
+    // nothing is reachable from a root that can't be read, which is already reported
+    let root_broken = graph.broken.contains(&start);
+    let mut unreachable: Vec<&PathBuf> = dir_slides_found
+        .difference(&yaml_slides_visited)
+        .filter(|_| !root_broken)
+        .collect();
+    unreachable.sort();
+    for slide in unreachable {
+        diagnostics.push(Diagnostic::new(Severity::Warning, Some(slide.clone()), "unreachable slide".into()));
+    }
+
+    check_keys(&graph, &start, &mut diagnostics);
+
+    diagnostics.sort_by(|a, b| (a.severity, &a.file, a.line).cmp(&(b.severity, &b.file, b.line)));
+    diagnostics
+}
+
+pub fn print_diagnostics(diagnostics: &[Diagnostic], format: CheckFormat) {
+    match format {
+        CheckFormat::Human => {
+            for diagnostic in diagnostics {
+                eprintln!("{diagnostic}");
+            }
+            let errors = diagnostics.iter().filter(|diagnostic| diagnostic.severity == Severity::Error).count();
+            eprintln!("{errors} errors, {} warnings", diagnostics.len() - errors);
+        }
+        CheckFormat::Json => match serde_json::to_string_pretty(diagnostics) {
+            Ok(json) => println!("{json}"),
+            Err(e) => eprintln!("could not serialize diagnostics: {e}"),
+        }
+    }
+}
+
+/// Every file the slide at `location` points to, resolved the way the player would load it
+pub fn referenced_paths(location: &Path, slide: &Slide) -> Vec<PathBuf> {
+    let asset = |path: &Path| prefix_path(&canonical_join(location, path));
+    let mut paths: Vec<PathBuf> = slide.images().into_iter().map(|(image_path, _)| asset(image_path)).collect();
+    for clickable in &slide.clickables {
+        paths.push(slide_file(&canonical_join(location, &clickable.slide_path)));
+        paths.extend(clickable.sound.as_deref().map(asset));
+    }
+    if let Some(auto_advance) = &slide.auto_advance {
+        paths.push(slide_file(&canonical_join(location, &auto_advance.slide_path)));
+    }
+    paths.extend(slide.music.as_deref().map(asset));
+    paths
+}
+
+/// Warns about elements that end up off the background, where they can't be seen or clicked, 
+/// and clickables stacked on top of each other
+fn check_placement(location: &Path, slide: &Slide, image_sizes: &HashMap<&Path, Coords<i32>>, diagnostics: &mut Vec<Diagnostic>) {
+    let Some(&background_size) = image_sizes.get(slide.background_path.as_path()) else { return };
+    let canvas_size = sheet_frame_size(background_size, slide.background_sprite_sheet.as_ref());
+    let mut warn = |message: String| {
+        diagnostics.push(Diagnostic::new(Severity::Warning, Some(slide_file(location)), message));
+    };
+
+    for (index, element) in slide.nonclickables.iter().enumerate() {
+        let Some(&image_size) = image_sizes.get(element.image_path.as_path()) else { continue };
+        let size = sheet_frame_size(image_size, element.sprite_sheet.as_ref());
+        let Coords { x, y } = position_asset(canvas_size, element.position, element.anchor, element.offset, size);
+        let rect = (x as i64, y as i64, (x + size.x) as i64, (y + size.y) as i64);
+        if visible_fraction(rect, canvas_size) == 0.0 {
+            warn(format!("nonclickable {index} ({:?}) is entirely off the background and never visible", element.image_path));
+        }
+    }
+
+    let mut hitboxes = Vec::new();
+    for (index, element) in slide.clickables.iter().enumerate() {
+        let image_rect = element.image_path.as_ref()
+            .and_then(|image_path| image_sizes.get(image_path.as_path()))
+            .map(|&image_size| {
+                let size = sheet_frame_size(image_size, element.sprite_sheet.as_ref());
+                let Coords { x, y } = position_asset(canvas_size, element.position, element.anchor, element.offset, size);
+                (x as i64, y as i64, (x + size.x) as i64, (y + size.y) as i64)
+            });
+        let rect = match &element.hotspot {
+            Some(hotspot) => hotspot_hitbox(canvas_size, hotspot).bounds(),
+            None => image_rect,
+        };
+        let Some(rect) = rect else { continue };
+
+        let name = format!("clickable {index} (to {:?})", element.slide_path);
+        let visible = visible_fraction(rect, canvas_size);
+        if visible == 0.0 {
+            warn(format!("{name} is entirely off the background and can't be clicked"));
+        } else if visible < 0.5 {
+            warn(format!("{name} is mostly off the background, only {:.0}% of it can be clicked", visible * 100.0));
+        }
+        hitboxes.push((index, element, name, rect));
+    }
+
+    for (position, (a_index, a, a_name, a_rect)) in hitboxes.iter().enumerate() {
+        for (_, b, b_name, b_rect) in &hitboxes[position + 1..] {
+            let overlapping = a_rect.0.max(b_rect.0) < a_rect.2.min(b_rect.2) 
+                && a_rect.1.max(b_rect.1) < a_rect.3.min(b_rect.3);
+            // clickables that are never shown together can share a spot
+            let exclusive = !a.must_have_keys.is_disjoint(&b.mustnt_have_keys) 
+                || !a.mustnt_have_keys.is_disjoint(&b.must_have_keys)
+                || !times_overlap(a, b);
+            if overlapping && !exclusive {
+                // clicks go to the first clickable in slide order, which is always `a`
+                warn(format!("{a_name} overlaps {b_name}, clicks on the overlap go to clickable {a_index}"));
+            }
+        }
+    }
+}
+
+/// How much of a `(x1, y1, x2, y2)` rectangle lies on the canvas, from `0.0` to `1.0`
+fn visible_fraction(rect: (i64, i64, i64, i64), canvas_size: Coords<i32>) -> f32 {
+    let (x1, y1, x2, y2) = rect;
+    let area = (x2 - x1) * (y2 - y1);
+    if area <= 0 {
+        return 0.0
+    }
+    let visible_width = (x2.min(canvas_size.x as i64) - x1.max(0)).max(0);
+    let visible_height = (y2.min(canvas_size.y as i64) - y1.max(0)).max(0);
+    (visible_width * visible_height) as f32 / area as f32
+}
+
+fn sheet_frame_size(image_size: Coords<i32>, sprite_sheet: Option<&SpriteSheet>) -> Coords<i32> {
+    match sprite_sheet {
+        Some(sprite_sheet) => {
+            let (columns, rows) = sprite_sheet.grid(image_size.x as u32, image_size.y as u32);
+            Coords { x: image_size.x / columns as i32, y: image_size.y / rows as i32 }
+        }
+        None => image_size,
+    }
+}
+
+/// Warns about sprite sheets whose grid doesn't fit their image, which get cut up differently than written
+fn check_sprite_sheets(location: &Path, slide: &Slide, image_sizes: &HashMap<&Path, Coords<i32>>, diagnostics: &mut Vec<Diagnostic>) {
+    let sheets = [(slide.background_path.as_path(), slide.background_sprite_sheet.as_ref())]
+        .into_iter()
+        .chain(slide.nonclickables.iter().map(|element| (element.image_path.as_path(), element.sprite_sheet.as_ref())))
+        .chain(slide.clickables.iter().filter_map(|element| Some((element.image_path.as_deref()?, element.sprite_sheet.as_ref()))));
+    for (image_path, sprite_sheet) in sheets {
+        let (Some(sprite_sheet), Some(&image_size)) = (sprite_sheet, image_sizes.get(image_path)) else { continue };
+        if let Some(message) = sprite_sheet_problem(sprite_sheet, image_size.x as u32, image_size.y as u32) {
+            diagnostics.push(Diagnostic::new(Severity::Warning, Some(slide_file(location)), format!("sprite sheet {image_path:?} {message}")));
+        }
+    }
+}
+
+fn sprite_sheet_problem(sprite_sheet: &SpriteSheet, width: u32, height: u32) -> Option<String> {
+    let (columns, rows) = sprite_sheet.grid(width, height);
+    let cells = columns.saturating_mul(rows);
+    if (columns, rows) != (sprite_sheet.columns, sprite_sheet.rows) {
+        Some(format!("has {} columns and {} rows, which a {width}x{height} image can't hold, {columns} and {rows} are used", 
+            sprite_sheet.columns, sprite_sheet.rows))
+    } else if !width.is_multiple_of(columns) || !height.is_multiple_of(rows) {
+        Some(format!("is {width}x{height}, which doesn't divide into {columns} columns and {rows} rows, the leftover pixels are cut off"))
+    } else if sprite_sheet.frame_count.is_some_and(|frame_count| frame_count > cells) {
+        Some(format!("asks for more frames than its {cells} cells, only {cells} are used"))
+    } else {
+        None
+    }
+}
+
+/// Whether two clickables are ever on screen at the same time
+fn times_overlap(a: &Clickable, b: &Clickable) -> bool {
+    let end = |clickable: &Clickable| clickable.duration.map_or(f32::INFINITY, |duration| clickable.delay + duration);
+    a.delay < end(b) && b.delay < end(a)
+}
+
+
+/// Fields that are only ignored while playing are errors here, since they're usually typos
+fn unknown_field_errors(file: &Path, unknown_fields: Vec<String>) -> impl Iterator<Item = Diagnostic> + '_ {
+    unknown_fields
+        .into_iter()
+        .map(|field| Diagnostic::new(Severity::Error, Some(file.into()), format!("unknown field {field:?}")))
+}
+
+
+/// Warns about hotkeys the game takes for itself before clickables see them, as long as players keep the default bindings
+fn check_hotkeys(location: &Path, slide: &Slide, diagnostics: &mut Vec<Diagnostic>) {
+    let bindings = Bindings::default();
+    let engine_keys = [
+        (&bindings.settings, "opens the settings"),
+        (&bindings.fullscreen, "toggles fullscreen"),
+        (&bindings.debug_overlay, "toggles the debug overlay while developing"),
+    ];
+    for (index, clickable) in slide.clickables.iter().enumerate() {
+        let Some(hotkey) = &clickable.hotkey else { continue };
+        for (binding, effect) in engine_keys {
+            if hotkey.eq_ignore_ascii_case(binding) {
+                diagnostics.push(Diagnostic::new(Severity::Warning, Some(slide_file(location)), format!(
+                    "clickable {index} (to {:?}) has hotkey {hotkey:?}, which {effect} instead", clickable.slide_path
+                )));
+            }
+        }
+    }
+}
+
+
+/// Walks every (slide, keys) state a player can actually be in, 
+/// to find what the key requirements make impossible
+fn check_keys(graph: &StoryGraph, start: &Path, diagnostics: &mut Vec<Diagnostic>) {
+    check_keys_up_to(graph, start, MAX_STATES, diagnostics);
+}
+
+fn check_keys_up_to(graph: &StoryGraph, start: &Path, max_states: usize, diagnostics: &mut Vec<Diagnostic>) {
+    let mut outgoing: HashMap<&Path, Vec<usize>> = HashMap::new();
+    for (index, edge) in graph.edges.iter().enumerate() {
+        outgoing.entry(&edge.from).or_default().push(index);
+    }
+
+    let mut states_seen: HashSet<(&Path, BTreeSet<String>)> = HashSet::new();
+    let mut to_visit = VecDeque::from([(start, BTreeSet::new())]);
+    let mut slides_reached: HashSet<&Path> = HashSet::new();
+    let mut edges_taken: HashSet<usize> = HashSet::new();
+    let mut search_finished = true;
+
+    while let Some((location, keys)) = to_visit.pop_front() {
+        if states_seen.contains(&(location, keys.clone())) {
+            continue
+        }
+        if states_seen.len() >= max_states {
+            diagnostics.push(Diagnostic::new(Severity::Warning, None, 
+                format!("stopped searching key combinations after {max_states} states, some key problems may be missed")));
+            search_finished = false;
+            break
+        }
+        slides_reached.insert(location);
+
+        for &index in outgoing.get(location).into_iter().flatten() {
+            let edge = &graph.edges[index];
+            if !edge.is_available(&keys) {
+                continue
+            }
+            edges_taken.insert(index);
+
+            let mut next_keys = keys.clone();
+            next_keys.extend(edge.adds_keys.iter().cloned());
+            next_keys.retain(|key| !edge.removes_keys.contains(key));
+            to_visit.push_back((&edge.to, next_keys));
+        }
+        states_seen.insert((location, keys));
+    }
+
+    // an unfinished search hasn't seen every state, so it can't say what's never reached
+    for slide in graph.slides.iter().filter(|_| search_finished) {
+        if !slides_reached.contains(slide.as_path()) {
+            diagnostics.push(Diagnostic::new(Severity::Warning, Some(slide_file(slide)), 
+                "slide is only reachable by ignoring key requirements".into()));
+        }
+    }
+
+    for (index, edge) in graph.edges.iter().enumerate() {
+        if search_finished
+            && matches!(edge.kind, EdgeKind::Click) 
+            && slides_reached.contains(edge.from.as_path()) 
+            && !edges_taken.contains(&index) 
+        {
+            diagnostics.push(Diagnostic::new(Severity::Warning, Some(slide_file(&edge.from)), 
+                format!("clickable leading to {:?} can never be used, its key requirements are never met", edge.to)));
+        }
+    }
+
+    let keys_set: BTreeSet<&String> = graph.edges.iter().flat_map(|edge| &edge.adds_keys).collect();
+    let keys_tested: BTreeSet<&String> = graph.edges.iter()
+        .flat_map(|edge| edge.must_have_keys.iter().chain(&edge.mustnt_have_keys))
+        .collect();
+    for key in keys_set.difference(&keys_tested) {
+        diagnostics.push(Diagnostic::new(Severity::Warning, None, format!("key {key:?} is set but never tested")));
+    }
+    for key in keys_tested.difference(&keys_set) {
+        diagnostics.push(Diagnostic::new(Severity::Warning, None, format!("key {key:?} is tested but never set")));
+    }
+
+    for slide in &graph.slides {
+        if !outgoing.contains_key(slide.as_path()) {
+            diagnostics.push(Diagnostic::new(Severity::Warning, Some(slide_file(slide)), 
+                "dead end slide has no clickables".into()));
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::Edge;
+
+    fn keys(keys: &[&str]) -> HashSet<String> {
+        keys.iter().map(|key| key.to_string()).collect()
+    }
+
+    /// A click from `from` to `to` that adds, needs and must not have the given keys
+    fn edge(from: &str, to: &str, adds: &[&str], must: &[&str], mustnt: &[&str]) -> Edge {
+        Edge {
+            from: from.into(),
+            to: to.into(),
+            kind: EdgeKind::Click,
+            adds_keys: keys(adds),
+            removes_keys: HashSet::new(),
+            must_have_keys: keys(must),
+            mustnt_have_keys: keys(mustnt),
+        }
+    }
+
+    fn graph(edges: Vec<Edge>) -> StoryGraph {
+        let slides = edges.iter().flat_map(|edge| [edge.from.clone(), edge.to.clone()]).collect();
+        StoryGraph { slides, broken: BTreeSet::new(), edges }
+    }
+
+    fn key_warnings(graph: &StoryGraph, max_states: usize) -> Vec<String> {
+        let mut diagnostics = Vec::new();
+        check_keys_up_to(graph, Path::new("/"), max_states, &mut diagnostics);
+        diagnostics.into_iter().map(|diagnostic| diagnostic.message).collect()
+    }
+
+    #[test]
+    fn visible_fraction_of_rects_on_and_off_the_canvas() {
+        let canvas = Coords { x: 100, y: 100 };
+        assert_eq!(visible_fraction((10, 10, 20, 20), canvas), 1.0);
+        assert_eq!(visible_fraction((-10, 0, 10, 10), canvas), 0.5);
+        assert_eq!(visible_fraction((90, 90, 110, 110), canvas), 0.25);
+        assert_eq!(visible_fraction((100, 0, 150, 50), canvas), 0.0);
+        assert_eq!(visible_fraction((-50, -50, 0, 0), canvas), 0.0);
+        assert_eq!(visible_fraction((10, 10, 10, 20), canvas), 0.0);
+    }
+
+    #[test]
+    fn overlap_names_the_clickable_that_gets_the_click() {
+        let slide: Slide = serde_yaml::from_str("
+background_path: bg.png
+clickables:
+  - slide_path: menu
+    hotkey: M
+  - slide_path: left
+    hotspot: !rect {from: {x: 0, y: 0}, to: {x: 60, y: 60}, units: pixels}
+  - slide_path: right
+    hotspot: !rect {from: {x: 40, y: 40}, to: {x: 100, y: 100}, units: pixels}
+").unwrap();
+        let image_sizes = HashMap::from([(Path::new("bg.png"), Coords { x: 100, y: 100 })]);
+        let mut diagnostics = Vec::new();
+        check_placement(Path::new("/"), &slide, &image_sizes, &mut diagnostics);
+        let messages: Vec<&str> = diagnostics.iter().map(|diagnostic| diagnostic.message.as_str()).collect();
+        assert_eq!(messages, ["clickable 1 (to \"left\") overlaps clickable 2 (to \"right\"), clicks on the overlap go to clickable 1"]);
+    }
+
+    #[test]
+    fn hotkeys_taken_by_the_game_are_reported() {
+        let slide: Slide = serde_yaml::from_str("
+background_path: bg.png
+clickables:
+  - slide_path: ..
+    hotkey: escape
+  - slide_path: ..
+    hotkey: Backspace
+  - slide_path: map
+    hotkey: F11
+").unwrap();
+        let mut diagnostics = Vec::new();
+        check_hotkeys(Path::new("/"), &slide, &mut diagnostics);
+        let messages: Vec<&str> = diagnostics.iter().map(|diagnostic| diagnostic.message.as_str()).collect();
+        assert_eq!(messages, [
+            "clickable 0 (to \"..\") has hotkey \"escape\", which opens the settings instead",
+            "clickable 2 (to \"map\") has hotkey \"F11\", which toggles fullscreen instead",
+        ]);
+    }
+
+    #[test]
+    fn sprite_sheets_that_dont_fit_their_image() {
+        let sheet = |columns, rows, frame_count| SpriteSheet { columns, rows, frame_count, fps: 10.0 };
+        assert_eq!(sprite_sheet_problem(&sheet(4, 2, None), 64, 32), None);
+        assert_eq!(sprite_sheet_problem(&sheet(4, 2, Some(8)), 64, 32), None);
+        assert!(sprite_sheet_problem(&sheet(100_000, 100_000, None), 64, 32).unwrap().contains("64 and 32 are used"));
+        assert!(sprite_sheet_problem(&sheet(0, 2, None), 64, 32).is_some());
+        assert!(sprite_sheet_problem(&sheet(5, 2, None), 64, 32).unwrap().contains("leftover pixels"));
+        assert!(sprite_sheet_problem(&sheet(4, 2, Some(9)), 64, 32).unwrap().contains("only 8 are used"));
+    }
+
+    #[test]
+    fn key_picked_up_on_another_branch_unlocks_the_door() {
+        let graph = graph(vec![
+            edge("/", "/forest", &[], &[], &[]),
+            edge("/forest", "/", &["lantern"], &[], &[]),
+            edge("/", "/cave", &[], &["lantern"], &[]),
+            edge("/cave", "/", &[], &[], &[]),
+        ]);
+        assert_eq!(key_warnings(&graph, MAX_STATES), Vec::<String>::new());
+    }
+
+    #[test]
+    fn key_that_is_never_set_locks_the_slide_away() {
+        let graph = graph(vec![
+            edge("/", "/vault", &[], &["code"], &[]),
+            edge("/vault", "/", &[], &[], &[]),
+        ]);
+        let warnings = key_warnings(&graph, MAX_STATES);
+        assert!(warnings.contains(&"slide is only reachable by ignoring key requirements".into()));
+        assert!(warnings.contains(&"clickable leading to \"/vault\" can never be used, its key requirements are never met".into()));
+        assert!(warnings.contains(&"key \"code\" is tested but never set".into()));
+    }
+
+    #[test]
+    fn contradictory_requirements_are_never_met() {
+        let graph = graph(vec![
+            edge("/", "/a", &["lamp"], &[], &[]),
+            edge("/a", "/b", &[], &["lamp"], &["lamp"]),
+            edge("/b", "/", &[], &[], &[]),
+        ]);
+        let warnings = key_warnings(&graph, MAX_STATES);
+        assert!(warnings.contains(&"clickable leading to \"/b\" can never be used, its key requirements are never met".into()));
+    }
+
+    #[test]
+    fn state_cap_warns_and_keeps_the_other_checks() {
+        let graph = graph(vec![
+            edge("/", "/a", &["a"], &[], &[]),
+            edge("/a", "/b", &["b"], &["a"], &[]),
+            edge("/b", "/", &["unused"], &[], &[]),
+        ]);
+        let warnings = key_warnings(&graph, 1);
+        assert!(warnings[0].starts_with("stopped searching key combinations after 1 states"));
+        assert!(warnings.contains(&"key \"unused\" is set but never tested".into()));
+        assert!(!warnings.iter().any(|warning| warning.contains("can never be used") || warning.contains("ignoring key requirements")));
+    }
+}
@@ -0,0 +1,111 @@
+use std::{io::{self, BufRead}, path::{Path, PathBuf}, thread};
+
+use image::ImageResult;
+use winit::event_loop::EventLoopProxy;
+
+use crate::{draw::{to_rgb_image, Canvas}, UserEvent};
+
+
+// Written by soweli Luna
+
+const HELP: &str = "\
+commands:
+  goto <path>          go to a slide, relative to the current one like a clickable's slide_path
+  give <key>           add a key
+  take <key>           remove a key
+  keys                 list the keys the player has
+  reload               read the slide and its images from disk again
+  screenshot [path]    save the slide as a PNG
+  help                 show this";
+
+
+/// A line typed into the developer console
+#[derive(Debug)]
+pub enum ConsoleCommand {
+    Goto(PathBuf),
+    Give(String),
+    Take(String),
+    Keys,
+    Reload,
+    Screenshot(Option<PathBuf>),
+}
+impl ConsoleCommand {
+    fn parse(line: &str) -> Result<Self, String> {
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or_default();
+        let argument = words.next();
+        if words.next().is_some() {
+            return Err(format!("too many arguments for {command:?}"))
+        }
+
+        match (command, argument) {
+            ("goto", Some(path)) => Ok(Self::Goto(path.into())),
+            ("give", Some(key)) => Ok(Self::Give(key.into())),
+            ("take", Some(key)) => Ok(Self::Take(key.into())),
+            ("keys", None) => Ok(Self::Keys),
+            ("reload", None) => Ok(Self::Reload),
+            ("screenshot", path) => Ok(Self::Screenshot(path.map(PathBuf::from))),
+            ("goto" | "give" | "take", None) => Err(format!("{command:?} needs an argument")),
+            ("keys" | "reload", Some(_)) => Err(format!("{command:?} takes no arguments")),
+            _ => Err(format!("unknown command {command:?}, try \"help\"")),
+        }
+    }
+}
+
+
+/// Reads commands from stdin on a background thread, sending each one to the event loop
+pub fn spawn_console(proxy: EventLoopProxy<UserEvent>) {
+    thread::spawn(move || {
+        println!("developer console, type \"help\" for commands");
+        for line in io::stdin().lock().lines() {
+            let Ok(line) = line else { return };
+            let line = line.trim();
+            if line.is_empty() {
+                continue
+            }
+            if line == "help" {
+                println!("{HELP}");
+                continue
+            }
+
+            match ConsoleCommand::parse(line) {
+                Ok(command) => {
+                    if proxy.send_event(UserEvent::Console(command)).is_err() {
+                        return
+                    }
+                }
+                Err(e) => println!("{e}"),
+            }
+        }
+    });
+}
+
+/// Saves what the canvas currently shows as a PNG
+pub fn save_screenshot(canvas: &Canvas, path: &Path) -> ImageResult<()> {
+    to_rgb_image(canvas.framebuffer(), canvas.size.x as u32, canvas.size.y as u32).save(path)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_with_and_without_arguments() {
+        assert!(matches!(ConsoleCommand::parse("goto ../forest"), Ok(ConsoleCommand::Goto(path)) if path == Path::new("../forest")));
+        assert!(matches!(ConsoleCommand::parse("give  lantern "), Ok(ConsoleCommand::Give(key)) if key == "lantern"));
+        assert!(matches!(ConsoleCommand::parse("take lantern"), Ok(ConsoleCommand::Take(key)) if key == "lantern"));
+        assert!(matches!(ConsoleCommand::parse("keys"), Ok(ConsoleCommand::Keys)));
+        assert!(matches!(ConsoleCommand::parse("reload"), Ok(ConsoleCommand::Reload)));
+        assert!(matches!(ConsoleCommand::parse("screenshot"), Ok(ConsoleCommand::Screenshot(None))));
+        assert!(matches!(ConsoleCommand::parse("screenshot shot.png"), Ok(ConsoleCommand::Screenshot(Some(path))) if path == Path::new("shot.png")));
+    }
+
+    #[test]
+    fn wrong_arguments_are_explained() {
+        assert_eq!(ConsoleCommand::parse("goto").unwrap_err(), "\"goto\" needs an argument");
+        assert_eq!(ConsoleCommand::parse("keys all").unwrap_err(), "\"keys\" takes no arguments");
+        assert_eq!(ConsoleCommand::parse("give a b").unwrap_err(), "too many arguments for \"give\"");
+        assert_eq!(ConsoleCommand::parse("teleport /").unwrap_err(), "unknown command \"teleport\", try \"help\"");
+    }
+}
@@ -1,155 +1,904 @@
-use std::{collections::HashSet, path::PathBuf, rc::Rc};
-use image::{imageops::overlay, DynamicImage, Pixel, Rgb};
-use softbuffer::Buffer;
-use winit::{dpi::PhysicalSize, window::Window};
-use crate::{filesystem::{read_image, FallbackAsset}, game::{Coords, Game}};
+use std::{collections::HashSet, mem, num::NonZeroU32, path::PathBuf, sync::Arc, time::{Duration, Instant}};
+use image::{Rgba, RgbImage};
+use softbuffer::Rect;
+use winit::{dpi::PhysicalSize, keyboard::Key};
+use crate::{filesystem::{canonical_join, read_frames, FallbackAsset, Frame}, game::{Coords, Game, Hotspot, Transition, Units}, settings::{Scaling, Settings}};
 
 
 // Written by soweli Luna
 
-#[derive(Default)]
+/// How often the window is redrawn while something is animating
+pub const FRAME_TIME: Duration = Duration::from_millis(16);
+
+const TIMER_HEIGHT: u32 = 6;
+/// Element timings are capped at a day, which is forever as far as a slide is concerned
+const MAX_TIMING_SECONDS: f32 = 86_400.0;
+const TIMER_COLOR: u32 = 0xffffff;
+
 pub struct Canvas {
-    dynamic_image: DynamicImage,
+    /// Images drawn over each other in order, starting with the background
+    layers: Vec<Layer>,
+    /// The composited canvas packed as `0RGB` pixels, the format softbuffer expects
+    framebuffer: Vec<u32>,
+    /// Regions that changed since the last `draw_to_buffer`
+    dirty: Vec<DirtyRect>,
+    /// When the slide was loaded, element timings count from here
+    loaded: Instant,
+    /// Set while the settings screen is open, element timings and the auto-advance timer stop here
+    paused_at: Option<Instant>,
+    auto_advance: Option<AutoAdvanceTimer>,
     pub buttons: Vec<Button>,
+    /// Clickables the player doesn't have the right keys for, kept for the debug overlay
+    pub hidden_buttons: Vec<Button>,
     pub size: Coords<i32>,
 }
 impl Canvas {
-    pub fn draw_to_buffer(&self, buffer: &mut Buffer<'_, Rc<Window>, Rc<Window>>, width: u32, height: u32) {
-        let image = self.dynamic_image.clone().into_rgb8();
-        for index in 0..(width * height) {
-            let y = index / width;
-            let x = index % width;
+    /// Copies the dirty parts of the canvas into the buffer, returning the damaged regions to present.
+    /// Everything is redrawn unless the buffer holds the previous frame, which softbuffer reports as an age of 1.
+    pub fn draw_to_buffer(&mut self, buffer: &mut [u32], buffer_age: u8, width: u32, height: u32) -> Vec<Rect> {
+        let dirty = if buffer_age == 1 {
+            mem::take(&mut self.dirty)
+        } else {
+            self.dirty.clear();
+            vec![DirtyRect::EVERYTHING]
+        };
+        let canvas_width = self.size.x as u32;
+        let canvas_height = self.size.y as u32;
 
-            let pixel = 
-                image
-                .get_pixel_checked(x, y)
-                .unwrap_or(&Rgb::<u8>::from([0, 0, 0]))
-                .to_rgb();
+        let mut damage = Vec::new();
+        for rect in dirty {
+            let x1 = rect.x.min(width);
+            let y1 = rect.y.min(height);
+            let x2 = rect.x.saturating_add(rect.width).min(width);
+            let y2 = rect.y.saturating_add(rect.height).min(height);
 
-            let pixel_channels = pixel.channels();
-            
-            let u32_pixel: u32 = 
-                (pixel_channels[0] as u32) << 16 |
-                (pixel_channels[1] as u32) << 8  |
-                (pixel_channels[2] as u32);
+            for y in y1..y2 {
+                let row = &mut buffer[(y * width) as usize..][..width as usize];
+                let visible_x2 = if y < canvas_height { x2.min(canvas_width).max(x1) } else { x1 };
+                if visible_x2 > x1 {
+                    let canvas_row = (y * canvas_width) as usize;
+                    row[x1 as usize..visible_x2 as usize].copy_from_slice(
+                        &self.framebuffer[canvas_row + x1 as usize..canvas_row + visible_x2 as usize]
+                    );
+                }
+                row[visible_x2 as usize..x2 as usize].fill(0);
+            }
 
-            buffer[index as usize] = u32_pixel;
-            
+            if let (Some(rect_width), Some(rect_height)) = (NonZeroU32::new(x2 - x1), NonZeroU32::new(y2 - y1)) {
+                damage.push(Rect { x: x1, y: y1, width: rect_width, height: rect_height });
+            }
         }
+        damage
     }
 
-    pub fn build(game: &Game) -> Self {
-        let mut canvas = Canvas::default();
-
-        canvas.dynamic_image = read_image(
-            game.location.join(game.slide.background_path.clone()), 
-            FallbackAsset::Background
+    pub fn build(game: &Game, settings: &Settings) -> Self {
+        let background = Layer::new(
+            read_frames(
+                canonical_join(&game.location, &game.slide.background_path), 
+                FallbackAsset::Background,
+                game.slide.background_sprite_sheet.as_ref(),
+            ),
+            Coords { x: 0, y: 0 },
+            Timing::default(),
+            settings,
         );
-        canvas.size = Coords {
-            x: canvas.dynamic_image.width() as i32,
-            y: canvas.dynamic_image.height() as i32,
+        let mut canvas = Canvas {
+            size: background.size(),
+            layers: vec![background],
+            framebuffer: Vec::new(),
+            dirty: Vec::new(),
+            loaded: Instant::now(),
+            paused_at: None,
+            auto_advance: game.slide.auto_advance.as_ref().map(|auto_advance| AutoAdvanceTimer {
+                after: Duration::from_millis(auto_advance.after_ms),
+                show_timer: auto_advance.show_timer,
+                action: Action {
+                    slide_path: auto_advance.slide_path.clone(),
+                    adds_keys: auto_advance.adds_keys.clone(),
+                    removes_keys: auto_advance.removes_keys.clone(),
+                    transition: auto_advance.transition,
+                    sound: None,
+                },
+            }),
+            buttons: Vec::new(),
+            hidden_buttons: Vec::new(),
         };
 
         for element in &game.slide.nonclickables {
-            let image = &read_image(
-                game.location.join(element.image_path.clone()), 
-                FallbackAsset::Nonclickable
+            let frames = read_frames(
+                canonical_join(&game.location, &element.image_path), 
+                FallbackAsset::Nonclickable,
+                element.sprite_sheet.as_ref(),
             );
-            let Coords {x, y} = canvas.position_asset(
+            let position = position_asset(
+                canvas.size,
                 element.position, 
                 element.anchor, 
                 element.offset, 
-                Coords { 
-                    x: image.width() as i32, 
-                    y: image.height() as i32,
-                }
+                frame_size(&frames),
             );
-            overlay(
-                &mut canvas.dynamic_image, 
-                image, 
-                x as i64, 
-                y as i64, 
-            )
+            canvas.layers.push(Layer::new(
+                frames, 
+                position, 
+                Timing::new(element.delay, element.duration, element.fade_in, settings),
+                settings,
+            ));
         } 
 
         for element in &game.slide.clickables {
-            if element.must_have_keys.is_subset(&game.keys) {
-                let image = &read_image(
-                    game.location.join(element.image_path.clone()), 
-                    FallbackAsset::Clickable
+            let available = element.is_available(&game.keys);
+            let timing = Timing::new(element.delay, element.duration, element.fade_in, settings);
+            let mut button = Button { 
+                slide_path: element.slide_path.clone(), 
+                adds_keys: element.adds_keys.clone(),
+                removes_keys: element.removes_keys.clone(),
+                hotkey: element.hotkey.clone(),
+                transition: element.transition,
+                sound: element.sound.clone(),
+                timing,
+                ..Default::default()
+            };
+
+            // clickables without an image or hotspot have no hitbox and can only be triggered by their hotkey
+            if let Some(hotspot) = &element.hotspot {
+                button.hitbox = hotspot_hitbox(canvas.size, hotspot);
+            }
+            if let Some(image_path) = &element.image_path {
+                let frames = read_frames(
+                    canonical_join(&game.location, image_path), 
+                    FallbackAsset::Clickable,
+                    element.sprite_sheet.as_ref(),
                 );
-                let Coords {x, y} = canvas.position_asset(
+                let size = frame_size(&frames);
+                let Coords {x, y} = position_asset(
+                    canvas.size,
                     element.position, 
                     element.anchor, 
                     element.offset, 
-                    Coords { 
-                        x: image.width() as i32, 
-                        y: image.height() as i32,
-                    }
+                    size,
                 );
-                canvas.buttons.push(Button { 
-                    slide_path: element.slide_path.clone(), 
-                    adds_keys: element.adds_keys.clone(),
-                    removes_keys: element.removes_keys.clone(),
-                    x1: x as i64, 
-                    y1: y as i64, 
-                    x2: x as i64 + image.width() as i64, 
-                    y2: y as i64 + image.height() as i64, 
-                });
-                overlay(
-                    &mut canvas.dynamic_image, 
-                    image, 
-                    x as i64, 
-                    y as i64,
-                )
-            }
-            
+                if element.hotspot.is_none() {
+                    button.hitbox = Hitbox::Rect { 
+                        x1: x as i64, 
+                        y1: y as i64, 
+                        x2: x as i64 + size.x as i64, 
+                        y2: y as i64 + size.y as i64, 
+                    };
+                }
+                if available {
+                    canvas.layers.push(Layer::new(frames, Coords {x, y}, timing, settings));
+                }
+            }
+
+            if available {
+                canvas.buttons.push(button);
+            } else {
+                canvas.hidden_buttons.push(button);
+            }
         } 
 
+        canvas.framebuffer = vec![0; (canvas.size.x * canvas.size.y) as usize];
+        for layer in &mut canvas.layers {
+            layer.opacity = layer.timing.opacity(0.0);
+            layer.frame = 0;
+        }
+        canvas.composite(canvas.bounds());
+        canvas.dirty = vec![DirtyRect::EVERYTHING];
         canvas
     }
 
-    pub fn click(&self, x: i64, y: i64) -> Option<(PathBuf, HashSet<String>, HashSet<String>)> {
-        for button in &self.buttons {
-            if (button.x1..button.x2).contains(&x) && (button.y1..button.y2).contains(&y) {
-                return Some((
-                    button.slide_path.clone(), 
-                    button.adds_keys.clone(), 
-                    button.removes_keys.clone()
-                ))
+    /// Fades elements in and out according to their timings and advances animations, 
+    /// returning whether anything visibly changed
+    pub fn update(&mut self) -> bool {
+        let elapsed = self.elapsed().as_secs_f32();
+        let bounds = self.bounds();
+        let mut changed = Vec::new();
+        for layer in &mut self.layers {
+            let opacity = layer.timing.opacity(elapsed);
+            let frame = layer.frame_at(elapsed);
+            if opacity != layer.opacity || (opacity > 0.0 && frame != layer.frame) {
+                layer.opacity = opacity;
+                layer.frame = frame;
+                changed.extend(layer.rect().intersect(bounds));
+            }
+        }
+
+        if self.auto_advance.as_ref().is_some_and(|auto_advance| auto_advance.show_timer) {
+            changed.extend(self.timer_rect().intersect(bounds));
+        }
+
+        for rect in &changed {
+            self.composite(*rect);
+        }
+        self.dirty.extend(&changed);
+        !changed.is_empty()
+    }
+
+    /// Shows the slide as it would be `elapsed` after it loaded. 
+    /// Returns `false`, leaving the slide as it was, if the clock can't go back that far.
+    #[must_use]
+    pub fn seek(&mut self, elapsed: Duration) -> bool {
+        let Some(loaded) = Instant::now().checked_sub(elapsed) else { return false };
+        self.loaded = loaded;
+        self.update();
+        true
+    }
+
+    /// Builds the slide again for changed settings, carrying on from the same point in its timings
+    pub fn rebuild(&mut self, game: &Game, settings: &Settings) {
+        let (loaded, paused_at) = (self.loaded, self.paused_at);
+        *self = Self::build(game, settings);
+        self.loaded = loaded;
+        self.paused_at = paused_at;
+        self.update();
+    }
+
+    /// Stops or restarts the clock that element timings and the auto-advance timer count with
+    pub fn set_paused(&mut self, paused: bool) {
+        match (paused, self.paused_at) {
+            (true, None) => self.paused_at = Some(Instant::now()),
+            (false, Some(paused_at)) => {
+                self.loaded += paused_at.elapsed();
+                self.paused_at = None;
+            }
+            _ => (),
+        }
+    }
+
+    /// How long the slide has been shown, not counting time spent paused
+    fn elapsed(&self) -> Duration {
+        self.paused_at.unwrap_or_else(Instant::now).saturating_duration_since(self.loaded)
+    }
+
+    /// When `update` next needs to be called, if any element is still waiting to change
+    pub fn next_update(&self) -> Option<Instant> {
+        if self.paused_at.is_some() {
+            return None
+        }
+        let elapsed = self.elapsed().as_secs_f32();
+        self.layers
+            .iter()
+            .flat_map(|layer| [layer.timing.next_change(elapsed), layer.next_frame(elapsed)])
+            .flatten()
+            .filter_map(|at| Duration::try_from_secs_f32(at).ok())
+            .map(|at| self.loaded + at)
+            .chain(self.auto_advance.as_ref().map(|auto_advance| {
+                if auto_advance.show_timer {
+                    Instant::now() + FRAME_TIME
+                } else {
+                    self.loaded + auto_advance.after
+                }
+            }))
+            .min()
+    }
+
+    /// How long is left before the slide advances on its own
+    pub fn remaining(&self) -> Option<Duration> {
+        self.auto_advance
+            .as_ref()
+            .map(|auto_advance| auto_advance.after.saturating_sub(self.elapsed()))
+    }
+
+    /// The action to take if the slide ran out of time, only returned once
+    pub fn timed_out(&mut self) -> Option<Action> {
+        if self.remaining()? > Duration::ZERO {
+            return None
+        }
+        self.auto_advance.take().map(|auto_advance| auto_advance.action)
+    }
+
+    /// The countdown bar along the bottom of the canvas
+    fn timer_rect(&self) -> DirtyRect {
+        let height = TIMER_HEIGHT.min(self.size.y as u32);
+        DirtyRect { x: 0, y: self.size.y as u32 - height, width: self.size.x as u32, height }
+    }
+
+    /// Redraws part of the framebuffer from every visible layer
+    fn composite(&mut self, rect: DirtyRect) {
+        let width = self.size.x as u32;
+        for y in rect.y..rect.y + rect.height {
+            self.framebuffer[(y * width + rect.x) as usize..][..rect.width as usize].fill(0);
+        }
+
+        for layer in &self.layers {
+            if layer.opacity <= 0.0 {
+                continue
+            }
+            let Some(overlap) = layer.rect().intersect(rect) else {
+                continue
+            };
+            let image = &layer.frames[layer.frame].image;
+            for y in overlap.y..overlap.y + overlap.height {
+                for x in overlap.x..overlap.x + overlap.width {
+                    let Rgba([r, g, b, a]) = *image.get_pixel(
+                        (x as i64 - layer.position.x) as u32, 
+                        (y as i64 - layer.position.y) as u32,
+                    );
+                    let pixel = &mut self.framebuffer[(y * width + x) as usize];
+                    if a == 255 && layer.opacity >= 1.0 {
+                        *pixel = pack_pixel(r, g, b);
+                    } else {
+                        *pixel = blend(*pixel, pack_pixel(r, g, b), a as f32 / 255.0 * layer.opacity);
+                    }
+                }
+            }
+        }
+
+        if let (Some(auto_advance), Some(remaining)) = (&self.auto_advance, self.remaining()) {
+            if let Some(overlap) = self.timer_rect().intersect(rect).filter(|_| auto_advance.show_timer) {
+                let filled = (self.size.x as f32 * remaining.as_secs_f32() / auto_advance.after.as_secs_f32().max(f32::EPSILON)) as u32;
+                for y in overlap.y..overlap.y + overlap.height {
+                    for x in overlap.x..overlap.x + overlap.width {
+                        let pixel = &mut self.framebuffer[(y * width + x) as usize];
+                        let color = if x < filled { TIMER_COLOR } else { 0 };
+                        *pixel = blend(*pixel, color, 0.75);
+                    }
+                }
+            }
+        }
+    }
+
+    fn bounds(&self) -> DirtyRect {
+        DirtyRect { x: 0, y: 0, width: self.size.x as u32, height: self.size.y as u32 }
+    }
+
+    pub fn click(&self, x: i64, y: i64) -> Option<Action> {
+        for button in self.visible_buttons() {
+            if button.hitbox.contains(x, y) {
+                return Some(button.action())
             }
         };
         None
     }
 
+    pub fn press(&self, key: &Key) -> Option<Action> {
+        for button in self.visible_buttons() {
+            if button.hotkey.as_ref().is_some_and(|hotkey| hotkey_matches(hotkey, key)) {
+                return Some(button.action())
+            }
+        };
+        None
+    }
+
+    fn visible_buttons(&self) -> impl Iterator<Item = &Button> {
+        let elapsed = self.elapsed().as_secs_f32();
+        self.buttons.iter().filter(move |button| button.timing.opacity(elapsed) > 0.0)
+    }
+
+    pub fn framebuffer(&self) -> &[u32] {
+        &self.framebuffer
+    }
+
+    /// Makes the next `draw_to_buffer` redraw the whole window
+    pub fn mark_all_dirty(&mut self) {
+        self.dirty = vec![DirtyRect::EVERYTHING];
+    }
+
     pub fn size(&self) -> PhysicalSize<u32> {
         PhysicalSize { 
-            width: self.dynamic_image.width(), 
-            height: self.dynamic_image.height() 
+            width: self.size.x as u32, 
+            height: self.size.y as u32, 
+        }
+    }
+}
+
+
+/// Where an element's top left corner goes on a canvas of `canvas_size`
+pub fn position_asset(
+    canvas_size: Coords<i32>,
+    position: Coords<f32>, 
+    anchor: Coords<f32>, 
+    offset: Coords<i32>, 
+    element_size: Coords<i32>
+) -> Coords<i32> {
+    let position_offset = (position * canvas_size.map(|t|{t as f32})).map(|t|{t as i32});
+    let anchor_offset = (anchor * element_size.map(|t|{t as f32})).map(|t|{t as i32});
+    position_offset + offset - anchor_offset
+}
+
+pub fn hotspot_hitbox(canvas_size: Coords<i32>, hotspot: &Hotspot) -> Hitbox {
+    let to_pixels = |point: Coords<f32>, units: Units| -> Coords<i64> {
+        match units {
+            Units::Relative => (point * canvas_size.map(|t|{t as f32})).map(|t|{t as i64}),
+            Units::Pixels => point.map(|t|{t as i64}),
+        }
+    };
+    match hotspot {
+        Hotspot::Rect { from, to, units } => {
+            let from = to_pixels(*from, *units);
+            let to = to_pixels(*to, *units);
+            Hitbox::Rect { 
+                x1: from.x.min(to.x), 
+                y1: from.y.min(to.y), 
+                x2: from.x.max(to.x), 
+                y2: from.y.max(to.y), 
+            }
+        }
+        Hotspot::Polygon { points, units } => {
+            Hitbox::Polygon(points.iter().map(|point| to_pixels(*point, *units)).collect())
+        }
+    }
+}
+
+
+/// Where the canvas goes in the window, and how much it's scaled up
+pub struct Viewport {
+    pub scale: f32,
+    pub x: i64,
+    pub y: i64,
+}
+impl Viewport {
+    pub fn new(scaling: Scaling, canvas_size: Coords<i32>, width: u32, height: u32) -> Self {
+        let fit = (width as f32 / canvas_size.x.max(1) as f32).min(height as f32 / canvas_size.y.max(1) as f32);
+        let scale = match scaling {
+            Scaling::None => return Self { scale: 1.0, x: 0, y: 0 },
+            Scaling::Integer => fit.floor().max(1.0),
+            Scaling::Fit => fit,
+        };
+        Self {
+            scale,
+            x: (width as i64 - (canvas_size.x as f32 * scale) as i64) / 2,
+            y: (height as i64 - (canvas_size.y as f32 * scale) as i64) / 2,
+        }
+    }
+
+    /// Whether the canvas is drawn as is in the top left corner
+    pub fn is_identity(&self) -> bool {
+        self.scale == 1.0 && self.x == 0 && self.y == 0
+    }
+
+    /// Converts a window position into canvas coordinates
+    pub fn to_canvas(&self, position: Coords<i64>) -> Coords<i64> {
+        Coords {
+            x: ((position.x - self.x) as f32 / self.scale).floor() as i64,
+            y: ((position.y - self.y) as f32 / self.scale).floor() as i64,
+        }
+    }
+
+    /// Converts a canvas position into window coordinates
+    pub fn to_window(&self, position: Coords<i64>) -> Coords<i64> {
+        Coords {
+            x: (position.x as f32 * self.scale) as i64 + self.x,
+            y: (position.y as f32 * self.scale) as i64 + self.y,
+        }
+    }
+
+    /// Draws a canvas sized frame into the whole window, scaled with nearest neighbour sampling
+    pub fn present(&self, frame: &[u32], frame_size: Coords<i32>, buffer: &mut [u32], width: u32, height: u32) {
+        let columns: Vec<Option<usize>> = (0..width)
+            .map(|x| {
+                let frame_x = ((x as i64 - self.x) as f32 / self.scale).floor() as i64;
+                (0..frame_size.x as i64).contains(&frame_x).then_some(frame_x as usize)
+            })
+            .collect();
+
+        for y in 0..height {
+            let row = &mut buffer[(y * width) as usize..][..width as usize];
+            let frame_y = ((y as i64 - self.y) as f32 / self.scale).floor() as i64;
+            if !(0..frame_size.y as i64).contains(&frame_y) {
+                row.fill(0);
+                continue
+            }
+            let frame_row = &frame[frame_y as usize * frame_size.x as usize..][..frame_size.x as usize];
+            for (pixel, column) in row.iter_mut().zip(&columns) {
+                *pixel = column.map_or(0, |column| frame_row[column]);
+            }
+        }
+    }
+}
+
+
+/// A slide's `auto_advance` counting down
+struct AutoAdvanceTimer {
+    after: Duration,
+    show_timer: bool,
+    action: Action,
+}
+
+
+/// Packs a pixel into the `0RGB` format softbuffer expects
+fn pack_pixel(r: u8, g: u8, b: u8) -> u32 {
+    (r as u32) << 16 | (g as u32) << 8 | (b as u32)
+}
+
+/// Unpacks a buffer of `0RGB` pixels, for saving it as an image
+pub fn to_rgb_image(buffer: &[u32], width: u32, height: u32) -> RgbImage {
+    let mut image = RgbImage::new(width, height);
+    for (pixel, packed) in image.pixels_mut().zip(buffer) {
+        pixel.0 = [(packed >> 16) as u8, (packed >> 8) as u8, *packed as u8];
+    }
+    image
+}
+
+/// Linearly interpolates between two `0RGB` pixels
+pub fn blend(a: u32, b: u32, t: f32) -> u32 {
+    let channel = |shift: u32| {
+        let a = ((a >> shift) & 0xff) as f32;
+        let b = ((b >> shift) & 0xff) as f32;
+        ((a + (b - a) * t) as u32) << shift
+    };
+    channel(16) | channel(8) | channel(0)
+}
+
+
+#[derive(Clone, Copy)]
+struct DirtyRect {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+impl DirtyRect {
+    /// The whole window, including anything outside the canvas
+    const EVERYTHING: Self = Self { x: 0, y: 0, width: u32::MAX, height: u32::MAX };
+
+    fn intersect(self, other: Self) -> Option<Self> {
+        let x1 = self.x.max(other.x);
+        let y1 = self.y.max(other.y);
+        let x2 = self.x.saturating_add(self.width).min(other.x.saturating_add(other.width));
+        let y2 = self.y.saturating_add(self.height).min(other.y.saturating_add(other.height));
+        (x1 < x2 && y1 < y2).then(|| Self { x: x1, y: y1, width: x2 - x1, height: y2 - y1 })
+    }
+}
+
+
+fn frame_size(frames: &[Frame]) -> Coords<i32> {
+    Coords {
+        x: frames[0].image.width() as i32,
+        y: frames[0].image.height() as i32,
+    }
+}
+
+
+/// An element image positioned on the canvas, animated if it has more than one frame
+struct Layer {
+    frames: Arc<Vec<Frame>>,
+    /// How long it takes to play every frame once
+    cycle: f32,
+    position: Coords<i64>,
+    timing: Timing,
+    /// What the layer was last composited with
+    opacity: f32,
+    frame: usize,
+}
+impl Layer {
+    /// Animations stay on their first frame with reduced motion
+    fn new(frames: Arc<Vec<Frame>>, position: Coords<i32>, timing: Timing, settings: &Settings) -> Self {
+        Self {
+            cycle: if settings.reduced_motion { 0.0 } else { frames.iter().map(|frame| frame.delay.as_secs_f32()).sum() },
+            frames,
+            position: position.map(|t|{t as i64}),
+            timing,
+            opacity: 0.0,
+            frame: 0,
         }
     }
 
-    fn position_asset(
-        &self, 
-        position: Coords<f32>, 
-        anchor: Coords<f32>, 
-        offset: Coords<i32>, 
-        element_size: Coords<i32>
-    ) -> Coords<i32> {
-        let position_offset = (position * self.size.map(|t|{t as f32})).map(|t|{t as i32});
-        let anchor_offset = (anchor * element_size.map(|t|{t as f32})).map(|t|{t as i32});
-        position_offset + offset - anchor_offset
+    fn size(&self) -> Coords<i32> {
+        frame_size(&self.frames)
+    }
+
+    /// Which frame is showing, animations loop from when the slide loaded
+    fn frame_at(&self, elapsed: f32) -> usize {
+        if self.frames.len() == 1 || self.cycle <= 0.0 {
+            return 0
+        }
+        let mut time_in_cycle = elapsed % self.cycle;
+        for (index, frame) in self.frames.iter().enumerate() {
+            time_in_cycle -= frame.delay.as_secs_f32();
+            if time_in_cycle < 0.0 {
+                return index
+            }
+        }
+        self.frames.len() - 1
+    }
+
+    /// When the next frame starts, if the layer is animated and on screen
+    fn next_frame(&self, elapsed: f32) -> Option<f32> {
+        if self.frames.len() == 1 || self.cycle <= 0.0 || self.timing.opacity(elapsed) <= 0.0 {
+            return None
+        }
+        let cycle_start = elapsed - elapsed % self.cycle;
+        let mut frame_end = cycle_start;
+        for frame in self.frames.iter() {
+            frame_end += frame.delay.as_secs_f32();
+            if frame_end > elapsed {
+                return Some(frame_end)
+            }
+        }
+        Some(cycle_start + self.cycle)
+    }
+
+    /// The part of the layer that isn't left of or above the canvas
+    fn rect(&self) -> DirtyRect {
+        let Coords { x: width, y: height } = self.size();
+        let x = self.position.x.max(0);
+        let y = self.position.y.max(0);
+        let x2 = (self.position.x + width as i64).max(x);
+        let y2 = (self.position.y + height as i64).max(y);
+        DirtyRect { 
+            x: x.min(u32::MAX as i64) as u32, 
+            y: y.min(u32::MAX as i64) as u32, 
+            width: (x2 - x).min(u32::MAX as i64) as u32, 
+            height: (y2 - y).min(u32::MAX as i64) as u32, 
+        }
     }
 }
 
 
+/// When an element is shown, in seconds since the slide loaded
+#[derive(Clone, Copy, Default)]
+pub struct Timing {
+    delay: f32,
+    /// Forever if unset
+    duration: Option<f32>,
+    fade_in: f32,
+}
+impl Timing {
+    /// Scales element timings by the player's text speed, skipping fade-ins with reduced motion
+    fn new(delay: f32, duration: Option<f32>, fade_in: f32, settings: &Settings) -> Self {
+        let speed = settings.text_speed.max(0.01);
+        // slides can say anything, `.inf` and huge values included, which must still fit in a `Duration`
+        let seconds = |value: f32| if value.is_nan() { 0.0 } else { (value / speed).clamp(0.0, MAX_TIMING_SECONDS) };
+        Self {
+            delay: seconds(delay),
+            duration: duration.map(seconds),
+            fade_in: if settings.reduced_motion { 0.0 } else { seconds(fade_in) },
+        }
+    }
+
+    fn opacity(&self, elapsed: f32) -> f32 {
+        if elapsed < self.delay || self.duration.is_some_and(|duration| elapsed >= self.delay + duration) {
+            0.0
+        } else if self.fade_in > 0.0 {
+            ((elapsed - self.delay) / self.fade_in).min(1.0)
+        } else {
+            1.0
+        }
+    }
+
+    /// When the opacity changes next, every frame while fading in
+    fn next_change(&self, elapsed: f32) -> Option<f32> {
+        if elapsed < self.delay {
+            Some(self.delay)
+        } else if elapsed < self.delay + self.fade_in {
+            Some(elapsed + FRAME_TIME.as_secs_f32())
+        } else {
+            self.duration
+                .map(|duration| self.delay + duration)
+                .filter(|&end| elapsed < end)
+        }
+    }
+}
+
+
+#[derive(Default)]
 pub struct Button {
     pub slide_path: PathBuf,
     pub adds_keys: HashSet<String>,
     pub removes_keys: HashSet<String>,
-    pub x1: i64,
-    pub y1: i64,
-    pub x2: i64,
-    pub y2: i64,
+    pub hotkey: Option<String>,
+    pub transition: Option<Transition>,
+    pub sound: Option<PathBuf>,
+    pub timing: Timing,
+    pub hitbox: Hitbox,
+}
+impl Button {
+    fn action(&self) -> Action {
+        Action {
+            slide_path: self.slide_path.clone(), 
+            adds_keys: self.adds_keys.clone(), 
+            removes_keys: self.removes_keys.clone(),
+            transition: self.transition,
+            sound: self.sound.clone(),
+        }
+    }
 }
 
+/// What happens when a button is clicked or its hotkey pressed
+pub struct Action {
+    pub slide_path: PathBuf,
+    pub adds_keys: HashSet<String>,
+    pub removes_keys: HashSet<String>,
+    pub transition: Option<Transition>,
+    pub sound: Option<PathBuf>,
+}
+
+
+#[derive(Default)]
+pub enum Hitbox {
+    /// Only reachable through a hotkey
+    #[default]
+    None,
+    Rect {
+        x1: i64,
+        y1: i64,
+        x2: i64,
+        y2: i64,
+    },
+    Polygon(Vec<Coords<i64>>),
+}
+impl Hitbox {
+    pub fn contains(&self, x: i64, y: i64) -> bool {
+        match self {
+            Self::None => false,
+            Self::Rect { x1, y1, x2, y2 } => (*x1..*x2).contains(&x) && (*y1..*y2).contains(&y),
+            Self::Polygon(points) => {
+                // even-odd rule, casting a ray towards +x
+                let mut inside = false;
+                for (index, a) in points.iter().enumerate() {
+                    let b = points[(index + 1) % points.len()];
+                    if (a.y > y) != (b.y > y) {
+                        let crossing_x = a.x as f64 + 
+                            (y - a.y) as f64 * (b.x - a.x) as f64 / (b.y - a.y) as f64;
+                        if (x as f64) < crossing_x {
+                            inside = !inside;
+                        }
+                    }
+                }
+                inside
+            }
+        }
+    }
+
+    /// The smallest `(x1, y1, x2, y2)` rectangle around the hitbox
+    pub fn bounds(&self) -> Option<(i64, i64, i64, i64)> {
+        match self {
+            Self::None => None,
+            Self::Rect { x1, y1, x2, y2 } => Some((*x1, *y1, *x2, *y2)),
+            Self::Polygon(points) => {
+                let xs = points.iter().map(|point| point.x);
+                let ys = points.iter().map(|point| point.y);
+                Some((xs.clone().min()?, ys.clone().min()?, xs.max()?, ys.max()?))
+            }
+        }
+    }
+}
+
+
+/// Hotkeys are either a single character (`"E"`) or the name of a special key (`"Space"`, `"Enter"`), 
+/// both compared case insensitively
+pub fn hotkey_matches(hotkey: &str, key: &Key) -> bool {
+    match key {
+        Key::Character(character) => character.eq_ignore_ascii_case(hotkey),
+        Key::Named(named) => format!("{named:?}").eq_ignore_ascii_case(hotkey),
+        _ => false,
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    /// A slide whose images are missing, so it's drawn with the fallback assets and needs no story on disk
+    fn auto_advancing_game() -> Game {
+        Game {
+            location: "/".into(),
+            keys: HashSet::new(),
+            slide: serde_yaml::from_str("
+                background_path: missing.png
+                clickables: []
+                auto_advance: {after_ms: 10000, slide_path: next}
+            ").unwrap(),
+        }
+    }
+
+    #[test]
+    fn paused_canvas_stops_the_auto_advance_timer() {
+        let game = auto_advancing_game();
+        let mut canvas = Canvas::build(&game, &Settings::default());
+        canvas.set_paused(true);
+        let remaining = canvas.remaining().unwrap();
+        thread::sleep(Duration::from_millis(30));
+        assert_eq!(canvas.remaining().unwrap(), remaining);
+        assert!(canvas.next_update().is_none());
+
+        canvas.set_paused(false);
+        thread::sleep(Duration::from_millis(30));
+        assert!(canvas.remaining().unwrap() < remaining);
+    }
+
+    #[test]
+    fn rebuilt_canvas_keeps_its_clock() {
+        let game = auto_advancing_game();
+        let mut canvas = Canvas::build(&game, &Settings::default());
+        assert!(canvas.seek(Duration::from_secs(4)));
+        canvas.rebuild(&game, &Settings::default());
+        assert!(canvas.remaining().unwrap() <= Duration::from_secs(6));
+    }
+
+
+    fn timing(delay: f32, duration: Option<f32>, fade_in: f32) -> Timing {
+        Timing::new(delay, duration, fade_in, &Settings::default())
+    }
+
+    #[test]
+    fn timing_fades_in_after_delay_and_hides_after_duration() {
+        let timing = timing(1.0, Some(2.0), 0.5);
+        assert_eq!(timing.opacity(0.5), 0.0);
+        assert_eq!(timing.opacity(1.25), 0.5);
+        assert_eq!(timing.opacity(2.0), 1.0);
+        assert_eq!(timing.opacity(3.0), 0.0);
+        assert_eq!(timing.next_change(0.5), Some(1.0));
+        assert_eq!(timing.next_change(2.0), Some(3.0));
+        assert_eq!(timing.next_change(3.5), None);
+    }
+
+    #[test]
+    fn timing_clamps_values_that_dont_fit_a_duration() {
+        let timing = timing(f32::INFINITY, Some(f32::MAX), f32::NAN);
+        assert_eq!(timing.delay, MAX_TIMING_SECONDS);
+        assert_eq!(timing.duration, Some(MAX_TIMING_SECONDS));
+        assert_eq!(timing.fade_in, 0.0);
+        assert_eq!(timing.opacity(f32::MAX), 0.0);
+
+        let negative = self::timing(-5.0, Some(-1.0), -1.0);
+        assert_eq!((negative.delay, negative.duration, negative.fade_in), (0.0, Some(0.0), 0.0));
+    }
+
+    #[test]
+    fn timing_scales_with_text_speed_and_skips_fades_with_reduced_motion() {
+        let settings = Settings { text_speed: 2.0, reduced_motion: true, ..Settings::default() };
+        let timing = Timing::new(4.0, Some(2.0), 1.0, &settings);
+        assert_eq!((timing.delay, timing.duration, timing.fade_in), (2.0, Some(1.0), 0.0));
+    }
+
+    #[test]
+    fn dirty_rects_intersect() {
+        let a = DirtyRect { x: 0, y: 0, width: 10, height: 10 };
+        let b = DirtyRect { x: 5, y: 5, width: 10, height: 10 };
+        let overlap = a.intersect(b).unwrap();
+        assert_eq!((overlap.x, overlap.y, overlap.width, overlap.height), (5, 5, 5, 5));
+
+        let everything = DirtyRect::EVERYTHING.intersect(a).unwrap();
+        assert_eq!((everything.width, everything.height), (10, 10));
+    }
+
+    #[test]
+    fn disjoint_dirty_rects_dont_intersect() {
+        let canvas = DirtyRect { x: 0, y: 0, width: 100, height: 100 };
+        let off_canvas = DirtyRect { x: 500, y: 0, width: 20, height: 20 };
+        assert!(canvas.intersect(off_canvas).is_none());
+        assert!(off_canvas.intersect(canvas).is_none());
+
+        let touching = DirtyRect { x: 100, y: 0, width: 20, height: 20 };
+        assert!(canvas.intersect(touching).is_none());
+    }
+
+    #[test]
+    fn polygon_hitbox_uses_even_odd_rule() {
+        // a U shape, the gap between its arms is outside
+        let hitbox = Hitbox::Polygon([(0, 0), (30, 0), (30, 30), (20, 30), (20, 10), (10, 10), (10, 30), (0, 30)]
+            .into_iter()
+            .map(|(x, y)| Coords { x, y })
+            .collect());
+        assert!(hitbox.contains(5, 20));
+        assert!(hitbox.contains(25, 20));
+        assert!(hitbox.contains(15, 5));
+        assert!(!hitbox.contains(15, 20));
+        assert!(!hitbox.contains(40, 5));
+        assert!(!hitbox.contains(-1, 5));
+        assert_eq!(hitbox.bounds(), Some((0, 0, 30, 30)));
+    }
+
+    #[test]
+    fn rect_hitbox_excludes_far_edges() {
+        let hitbox = Hitbox::Rect { x1: 10, y1: 10, x2: 20, y2: 20 };
+        assert!(hitbox.contains(10, 10));
+        assert!(!hitbox.contains(20, 15));
+        assert!(!Hitbox::None.contains(0, 0));
+    }
+
+    #[test]
+    fn hotspot_units_and_reversed_corners() {
+        let canvas_size = Coords { x: 200, y: 100 };
+        let relative = Hotspot::Rect { from: Coords { x: 0.5, y: 0.5 }, to: Coords { x: 0.25, y: 0.1 }, units: Units::Relative };
+        assert!(matches!(hotspot_hitbox(canvas_size, &relative), Hitbox::Rect { x1: 50, y1: 10, x2: 100, y2: 50 }));
+
+        let pixels = Hotspot::Rect { from: Coords { x: 5.0, y: 5.0 }, to: Coords { x: 15.0, y: 25.0 }, units: Units::Pixels };
+        assert!(matches!(hotspot_hitbox(canvas_size, &pixels), Hitbox::Rect { x1: 5, y1: 5, x2: 15, y2: 25 }));
+    }
+}
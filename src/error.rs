@@ -0,0 +1,82 @@
+use std::{fmt, io, path::PathBuf};
+
+use image::ImageError;
+
+
+// Written by soweli Luna
+
+/// Everything that can go wrong loading a story, so callers can tell a missing file from a broken one
+#[derive(Debug)]
+pub enum Error {
+    /// No file or directory at `path`, `source` is `None` unless it was looked for on disk,
+    /// in the story folder of a portable build
+    Missing {
+        path: PathBuf,
+        source: Option<io::Error>,
+    },
+    /// The file exists but isn't a valid image, animation or sound
+    Decode {
+        path: PathBuf,
+        message: String,
+    },
+    /// A `slide.yaml` that doesn't parse, `source` knows the line and column
+    Yaml {
+        path: PathBuf,
+        source: serde_yaml::Error,
+    },
+    /// A path that leads outside the story root
+    PathEscape {
+        path: PathBuf,
+    },
+    /// A path that only matches a file when ignoring case
+    CaseMismatch {
+        path: PathBuf,
+        actual: PathBuf,
+    },
+    NotADirectory {
+        path: PathBuf,
+    },
+    /// A file the engine creates, like a new story, couldn't be written
+    Write {
+        path: PathBuf,
+        source: io::Error,
+    },
+    /// The sound device failed, or a sound couldn't be played
+    #[cfg_attr(not(feature = "audio"), allow(dead_code))]
+    Audio(String),
+}
+impl Error {
+    pub fn from_image(path: PathBuf, error: ImageError) -> Self {
+        match error {
+            ImageError::IoError(source) if source.kind() == io::ErrorKind::NotFound => {
+                Self::Missing { path, source: Some(source) }
+            }
+            error => Self::Decode { path, message: error.to_string() },
+        }
+    }
+}
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing { path, source: Some(source) } => write!(f, "could not find {path:?}: {source}"),
+            Self::Missing { path, source: None } => write!(f, "could not find {path:?}"),
+            Self::Decode { path, message } => write!(f, "could not decode {path:?}: {message}"),
+            Self::Yaml { path, source } => write!(f, "could not read {path:?}: {source}"),
+            Self::PathEscape { path } => write!(f, "{path:?} escapes the story root"),
+            Self::CaseMismatch { path, actual } => write!(f, "{path:?} differs in case from {actual:?}"),
+            Self::NotADirectory { path } => write!(f, "{path:?} is not a directory"),
+            Self::Write { path, source } => write!(f, "could not write {path:?}: {source}"),
+            Self::Audio(message) => write!(f, "{message}"),
+        }
+    }
+}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Missing { source: Some(source), .. } => Some(source),
+            Self::Yaml { source, .. } => Some(source),
+            Self::Write { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
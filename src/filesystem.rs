@@ -1,59 +1,282 @@
-use std::{collections::HashSet, fs, path::{Component, Path, PathBuf}};
+use std::{collections::HashSet, ffi::{OsStr, OsString}, fs, io::{BufReader, Read}, path::{Component, Path, PathBuf}, sync::{atomic::{AtomicBool, Ordering}, mpsc::{self, Sender}, Arc, LazyLock, Mutex}, thread, time::Duration};
 
-use image::DynamicImage;
+use image::{codecs::{gif::GifDecoder, png::PngDecoder, webp::WebPDecoder}, AnimationDecoder, DynamicImage, GenericImageView, ImageError, RgbaImage};
 use include_dir::{include_dir, Dir};
-use serde::{Deserialize, Serialize};
+use schemars::{schema_for, JsonSchema};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_yaml as yaml;
 
-use crate::game::{Clickable, Coords, Nonclickable};
+use crate::cache::IMAGE_CACHE;
+use crate::error::Error;
+use crate::report::report_error;
+use crate::pack::PACK;
+use crate::game::{AutoAdvance, Clickable, Nonclickable, SpriteSheet, Transition};
 
 
 pub static ASSETS: Dir<'_> = include_dir!("$CARGO_MANIFEST_DIR/assets");
 
-const ROOT_PREFIX: &str = "story/";
+pub const ROOT_PREFIX: &str = "story/";
 const YAML_FILENAME: &str = "slide.yaml";
 
+/// Set by `--strict`, refuses to load assets that fail `validate_path` instead of only warning in `check`
+pub static STRICT_PATHS: AtomicBool = AtomicBool::new(false);
+
 
 // Written by soweli Luna
 
-pub fn read_image(path: PathBuf, fallback: FallbackAsset) -> DynamicImage {
-    let full_path = prefix_path(&path);
+/// Reads every frame of an image through the image cache, substituting the fallback asset if it can't be loaded
+pub fn read_image(path: PathBuf, fallback: FallbackAsset) -> Arc<Vec<Frame>> {
+    match cached_frames(&prefix_path(&path)) {
+        Ok(frames) => frames,
+        Err(e) => {
+            report_error(&e);
+            fallback_image(fallback)
+        }
+    }
+}
 
-    if cfg!(feature="portable") {
+/// Every frame of an image, decoding and caching it if it isn't cached yet. Failures aren't cached.
+fn cached_frames(full_path: &Path) -> Result<Arc<Vec<Frame>>, Error> {
+    let key = cache_key(full_path);
+    if let Some(frames) = IMAGE_CACHE.lock().unwrap().get(&key) {
+        return Ok(frames)
+    }
+    let frames = Arc::new(decode_frames(full_path)?);
+    log::trace!("decoded {full_path:?}");
+    IMAGE_CACHE.lock().unwrap().insert(key, frames.clone());
+    Ok(frames)
+}
 
-        match image::open(&full_path) {
-            Ok(val) => val,
-            Err(e) => {
-                eprintln!("could not find asset {full_path:?}: {e}");
-                image::load_from_memory(
-                    ASSETS.get_file(fallback.clone().into_pathbuf())
-                    .expect("could not find static fallback asset")
-                    .contents()
-                ).expect("could not load static fallback asset")
-            }
-        }
+/// Where an image is kept in the cache, so `story/forest/../bg.png` and `story/bg.png` are decoded once
+fn cache_key(full_path: &Path) -> PathBuf {
+    resolve_dots(full_path).unwrap_or_else(|| full_path.into())
+}
+
+pub fn decode_image(full_path: &Path) -> Result<DynamicImage, Error> {
+    enforce_strict(full_path)?;
+    if let Some(pack) = PACK.get() {
+        let data = pack.file(full_path).ok_or(Error::Missing { path: full_path.into(), source: None })?;
+        image::load_from_memory(data).map_err(|e| Error::from_image(full_path.into(), e))
+    } else if cfg!(feature="portable") {
+        image::open(full_path).map_err(|e| Error::from_image(full_path.into(), e))
+    } else {    //for static assets
+        let file = ASSETS.get_file(full_path)
+            .ok_or(Error::Missing { path: full_path.into(), source: None })?;
+        image::load_from_memory(file.contents())
+            .map_err(|e| Error::from_image(full_path.into(), e))
+    }
+}
+
+/// Parses a YAML file of the story. Fields `T` doesn't have are ignored rather than refused,
+/// so stories keep loading across versions, and are returned as paths like `clickables[0].hotpsot`.
+pub fn read_yaml_file<T: DeserializeOwned>(full_path: &Path) -> Result<(T, Vec<String>), Error> {
+    parse_yaml(full_path, &read_asset(full_path)?)
+}
 
+fn parse_yaml<T: DeserializeOwned>(full_path: &Path, data: &[u8]) -> Result<(T, Vec<String>), Error> {
+    let mut unknown_fields = Vec::new();
+    let value = serde_ignored::deserialize(yaml::Deserializer::from_slice(data), |field| unknown_fields.push(field_path(&field)))
+        .map_err(|e| Error::Yaml { path: full_path.into(), source: e })?;
+    Ok((value, unknown_fields))
+}
+
+/// Writes a field's path the way serde_yaml's errors do
+fn field_path(path: &serde_ignored::Path) -> String {
+    match path {
+        serde_ignored::Path::Root => String::new(),
+        serde_ignored::Path::Seq { parent, index } => format!("{}[{index}]", field_path(parent)),
+        serde_ignored::Path::Map { parent, key } => match field_path(parent) {
+            parent if parent.is_empty() => key.clone(),
+            parent => format!("{parent}.{key}"),
+        },
+        serde_ignored::Path::Some { parent }
+        | serde_ignored::Path::NewtypeStruct { parent }
+        | serde_ignored::Path::NewtypeVariant { parent } => field_path(parent),
+    }
+}
+
+/// Reads the raw bytes of any story asset, resolved the same way as images
+pub fn read_asset(full_path: &Path) -> Result<Vec<u8>, Error> {
+    enforce_strict(full_path)?;
+    if let Some(pack) = PACK.get() {
+        pack.file(full_path)
+            .map(<[u8]>::to_vec)
+            .ok_or(Error::Missing { path: full_path.into(), source: None })
+    } else if cfg!(feature="portable") {
+        fs::read(full_path).map_err(|e| Error::Missing { path: full_path.into(), source: Some(e) })
     } else {    //for static assets
+        ASSETS.get_file(full_path)
+            .map(|file| file.contents().to_vec())
+            .ok_or(Error::Missing { path: full_path.into(), source: None })
+    }
+}
 
-        match image::load_from_memory(
-            ASSETS.get_file(&full_path)
-            .unwrap_or_else(|| {
-                eprintln!("could not find static asset {full_path:?}");
-                ASSETS.get_file(fallback.clone().into_pathbuf())
-                .expect("could not find static fallback asset")
-            }).contents()
-        ) {
-            Ok(val) => val,
-            Err(e) => {
-                eprintln!("could not decode static asset {full_path:?}: {e}");
-                image::load_from_memory(
-                    ASSETS.get_file(fallback.into_pathbuf())
-                    .expect("could not find static fallback asset")
-                    .contents()
-                ).expect("could not decode static fallback asset")
+/// One still image of an animation, shown for `delay` before moving on to the next
+pub struct Frame {
+    pub image: RgbaImage,
+    pub delay: Duration,
+}
+
+/// Reads every frame of an image, either by slicing up a sprite sheet or 
+/// from an animated GIF, APNG or WebP. Anything else is a single frame.
+pub fn read_frames(path: PathBuf, fallback: FallbackAsset, sprite_sheet: Option<&SpriteSheet>) -> Arc<Vec<Frame>> {
+    let frames = read_image(path, fallback);
+    match sprite_sheet {
+        // only a copy, the decoding it starts from is cached
+        Some(sprite_sheet) => Arc::new(slice_sprite_sheet(&frames[0].image, sprite_sheet)),
+        None => frames,
+    }
+}
+
+/// Decodes every frame of an animated GIF, APNG or WebP, or the one frame of anything else
+fn decode_frames(full_path: &Path) -> Result<Vec<Frame>, Error> {
+    enforce_strict(full_path)?;
+    // missing files are left for decode_image to report
+    let animation = if let Some(pack) = PACK.get() {
+        match pack.file(full_path) {
+            Some(data) => decode_animation(data, full_path)?,
+            None => None,
+        }
+    } else if cfg!(feature="portable") {
+        match fs::File::open(full_path) {
+            Ok(file) => decode_animation(BufReader::new(file), full_path)?,
+            Err(_) => None,
+        }
+    } else {    //for static assets
+        match ASSETS.get_file(full_path) {
+            Some(file) => decode_animation(file.contents(), full_path)?,
+            None => None,
+        }
+    };
+
+    match animation {
+        Some(frames) => Ok(frames),
+        None => Ok(vec![Frame { image: decode_image(full_path)?.to_rgba8(), delay: Duration::ZERO }]),
+    }
+}
+
+/// Decodes the frames of animated files, or `None` for files with a single frame
+fn decode_animation<R: Read>(reader: R, full_path: &Path) -> Result<Option<Vec<Frame>>, Error> {
+    let decode_error = |e: ImageError| Error::Decode { path: full_path.into(), message: e.to_string() };
+    let extension = full_path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_ascii_lowercase());
+
+    let frames = match extension.as_deref() {
+        Some("gif") => GifDecoder::new(reader).and_then(|decoder| decoder.into_frames().collect_frames()),
+        Some("png") => {
+            let decoder = PngDecoder::new(reader).map_err(decode_error)?;
+            if !decoder.is_apng() {
+                return Ok(None)
             }
+            decoder.apng().into_frames().collect_frames()
         }
+        Some("webp") => {
+            let decoder = WebPDecoder::new(reader).map_err(decode_error)?;
+            if !decoder.has_animation() {
+                return Ok(None)
+            }
+            decoder.into_frames().collect_frames()
+        }
+        _ => return Ok(None),
+    }.map_err(decode_error)?;
 
+    if frames.len() <= 1 {
+        return Ok(None)
+    }
+    Ok(Some(frames.into_iter().map(|frame| {
+        let delay = Duration::from(frame.delay());
+        Frame {
+            image: frame.into_buffer(),
+            // browsers treat zero delays as 100ms, so authors expect it too
+            delay: if delay.is_zero() { Duration::from_millis(100) } else { delay },
+        }
+    }).collect()))
+}
+
+fn slice_sprite_sheet(image: &RgbaImage, sprite_sheet: &SpriteSheet) -> Vec<Frame> {
+    let (columns, rows) = sprite_sheet.grid(image.width(), image.height());
+    let frame_width = image.width() / columns;
+    let frame_height = image.height() / rows;
+    let cells = columns.saturating_mul(rows);
+    let frame_count = sprite_sheet.frame_count.unwrap_or(cells).clamp(1, cells);
+    let delay = Duration::from_secs_f32(1.0 / sprite_sheet.fps.max(0.001));
+
+    (0..frame_count).map(|index| Frame {
+        image: image.view(
+            index % columns * frame_width, 
+            index / columns * frame_height, 
+            frame_width, 
+            frame_height,
+        ).to_image(),
+        delay,
+    }).collect()
+}
+
+fn fallback_image(fallback: FallbackAsset) -> Arc<Vec<Frame>> {
+    let fallback_path = fallback.into_pathbuf();
+    if let Some(frames) = IMAGE_CACHE.lock().unwrap().get(&fallback_path) {
+        return frames
+    }
+
+    let image = image::load_from_memory(
+        ASSETS.get_file(&fallback_path)
+        .expect("could not find static fallback asset")
+        .contents()
+    ).expect("could not decode static fallback asset");
+    let frames = Arc::new(vec![Frame { image: image.to_rgba8(), delay: Duration::ZERO }]);
+    IMAGE_CACHE.lock().unwrap().insert(fallback_path, frames.clone());
+    frames
+}
+
+/// Slides queued for preloading, so one queued twice is only read once
+static PRELOAD_QUEUE: LazyLock<Mutex<HashSet<PathBuf>>> = LazyLock::new(Default::default);
+
+/// Feeds the one preload worker, which decodes slides' images in the background one at a time
+static PRELOADER: LazyLock<Mutex<Sender<PathBuf>>> = LazyLock::new(|| {
+    let (sender, receiver) = mpsc::channel::<PathBuf>();
+    thread::spawn(move || {
+        for target in receiver {
+            preload_slide(&target);
+            PRELOAD_QUEUE.lock().unwrap().remove(&target);
+        }
+    });
+    Mutex::new(sender)
+});
+
+/// Queues the images of every slide reachable from `slide` for decoding on the preload worker, 
+/// so they are already cached when the player clicks through
+pub fn preload_reachable(location: &Path, slide: &Slide) {
+    let targets = slide.clickables
+        .iter()
+        .map(|clickable| &clickable.slide_path)
+        .chain(slide.auto_advance.iter().map(|auto_advance| &auto_advance.slide_path))
+        .map(|slide_path| canonical_join(location, slide_path));
+
+    for target in targets {
+        if !PRELOAD_QUEUE.lock().unwrap().insert(target.clone()) {
+            continue
+        }
+        log::debug!("preloading {target:?}");
+        if PRELOADER.lock().unwrap().send(target).is_err() {
+            log::warn!("the preload worker stopped, slides will be decoded as they're shown");
+            return
+        }
+    }
+}
+
+fn preload_slide(location: &Path) {
+    let Ok(slide) = Slide::read_yaml(location) else { return };
+    for (image_path, _) in slide.images() {
+        let full_path = prefix_path(&canonical_join(location, image_path));
+        if IMAGE_CACHE.lock().unwrap().contains(&cache_key(&full_path)) {
+            continue
+        }
+        // reported when the slide is actually shown, if it's still broken then
+        if let Err(e) = cached_frames(&full_path) {
+            log::debug!("could not preload {full_path:?}: {e}");
+        }
     }
 }
 #[derive(Clone)]
@@ -73,20 +296,22 @@ impl FallbackAsset {
 }
 
 
-pub fn prefix_path(path: &PathBuf) -> PathBuf {
+/// The `slide.yaml` of the slide at `location`
+pub fn slide_file(location: &Path) -> PathBuf {
+    prefix_path(location).join(YAML_FILENAME)
+}
+
+pub fn prefix_path(path: &Path) -> PathBuf {
     let root_prefix: PathBuf = ROOT_PREFIX.into(); 
     root_prefix.join(
         path
         .strip_prefix("/")
-        .unwrap_or(
-            path
-            .as_path()
-        )
+        .unwrap_or(path)
     )
 }
 
-pub fn canonical_join(lhs: &PathBuf, rhs: &PathBuf) -> PathBuf {
-    let rhs_first_component= rhs.components().nth(0);
+pub fn canonical_join(lhs: &Path, rhs: &Path) -> PathBuf {
+    let rhs_first_component= rhs.components().next();
     match rhs_first_component {
         Some(Component::CurDir) => {
             lhs.join(rhs.strip_prefix("./").unwrap_or(
@@ -102,117 +327,241 @@ pub fn canonical_join(lhs: &PathBuf, rhs: &PathBuf) -> PathBuf {
     }
 }
 
+/// Checks that a path from `prefix_path` stays inside the story root, 
+/// and that its case matches the files it names exactly, since `include_dir` is case sensitive
+pub fn validate_path(full_path: &Path) -> Result<(), Error> {
+    let normalized = resolve_dots(full_path).ok_or(Error::PathEscape { path: full_path.into() })?;
+    match actual_case(&normalized) {
+        Some(actual) if actual != normalized => Err(Error::CaseMismatch { path: full_path.into(), actual }),
+        _ => Ok(()),
+    }
+}
+
+/// `full_path` with `.` and `..` worked out and spelled the way the story spells it,
+/// so that different paths to the same file compare equal
+pub fn normalize_path(full_path: &Path) -> PathBuf {
+    match resolve_dots(full_path) {
+        Some(resolved) => actual_case(&resolved).unwrap_or(resolved),
+        None => full_path.into(),
+    }
+}
+
+/// `full_path` with `.` and `..` worked out, or `None` if that leaves the story root
+fn resolve_dots(full_path: &Path) -> Option<PathBuf> {
+    let mut resolved = PathBuf::new();
+    for component in full_path.components() {
+        match component {
+            Component::Normal(name) => resolved.push(name),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !resolved.pop() {
+                    return None
+                }
+            }
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    resolved.starts_with(ROOT_PREFIX).then_some(resolved)
+}
+
+fn enforce_strict(full_path: &Path) -> Result<(), Error> {
+    if STRICT_PATHS.load(Ordering::Relaxed) {
+        validate_path(full_path)
+    } else {
+        Ok(())
+    }
+}
+
+/// The path as it's actually spelled in the story, if it exists ignoring case
+fn actual_case(full_path: &Path) -> Option<PathBuf> {
+    actual_case_in(full_path, names_in_story)
+}
+
+/// Names of the files and folders in `directory`, wherever the story is read from
+fn names_in_story(directory: &Path) -> Option<Vec<OsString>> {
+    if let Some(pack) = PACK.get() {
+        Some(pack.names_in(directory).into_iter().map(OsStr::to_os_string).collect())
+    } else if cfg!(feature="portable") {
+        let directory = if directory.as_os_str().is_empty() { Path::new(".") } else { directory };
+        Some(fs::read_dir(directory).ok()?.flatten().map(|entry| entry.file_name()).collect())
+    } else {    // for static assets
+        let directory = if directory.as_os_str().is_empty() { Some(&ASSETS) } else { ASSETS.get_dir(directory) };
+        Some(directory?.entries().iter().filter_map(|entry| entry.path().file_name()).map(OsStr::to_os_string).collect())
+    }
+}
 
+/// Like `actual_case`, listing folders with `names_in`
+fn actual_case_in(full_path: &Path, names_in: impl Fn(&Path) -> Option<Vec<OsString>>) -> Option<PathBuf> {
+    let mut actual = PathBuf::new();
+    for component in full_path.components() {
+        let wanted = component.as_os_str();
+        let names = names_in(&actual)?;
+        let exact = names.iter().find(|name| *name == wanted);
+        let folded = names.iter().find(|name| name.eq_ignore_ascii_case(wanted));
+        actual.push(exact.or(folded)?);
+    }
+    Some(actual)
+}
 
 
 
-#[derive(Serialize, Deserialize, Debug)]
+
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
+#[schemars(deny_unknown_fields)]
 pub struct Slide {
     pub background_path: PathBuf, 
     #[serde(default)]
+    pub background_sprite_sheet: Option<SpriteSheet>,
+    #[serde(default)]
     pub nonclickables: Vec<Nonclickable>, 
     pub clickables: Vec<Clickable>,
+    /// Used when arriving at this slide, unless the clickable leading here has its own
+    #[serde(default)]
+    pub transition: Option<Transition>,
+    #[serde(default)]
+    pub auto_advance: Option<AutoAdvance>,
+    /// Looping OGG or WAV track, which keeps playing across slides with the same music
+    #[serde(default)]
+    pub music: Option<PathBuf>,
 }
 impl Slide {
-    pub fn read_yaml(input: &PathBuf) -> Result<Self, String> {
-        let full_path = prefix_path(input).join(YAML_FILENAME);
-        if cfg!(feature="portable") {
-            match yaml::from_str(
-                &match fs::read_to_string(&full_path) {
-                    Ok(val) => val,
-                    Err(e) => {Err(format!("could not find {full_path:?}: {e}"))?},
-                }
-            ) {
-                Ok(val) => Ok(val),
-                Err(e) => Err(format!("could not read {full_path:?}: {e}")),
-            }
-        } else {    //for static assets
-            match yaml::from_slice(
-                &ASSETS.get_file(&full_path).ok_or(&format!("could not find static {full_path:?}"))?.contents()
-            ) {
-                Ok(val) => Ok(val),
-                Err(e) => Err(format!("could not read static {full_path:?}: {e}")),
-            }
-        }
-        
+    /// Reads the slide at `input`, ignoring fields that aren't known
+    pub fn read_yaml(input: &Path) -> Result<Self, Error> {
+        Self::read_yaml_checked(input).map(|(slide, _)| slide)
+    }
+
+    /// Like `read_yaml`, also giving the fields that were ignored because they aren't known
+    pub fn read_yaml_checked(input: &Path) -> Result<(Self, Vec<String>), Error> {
+        read_yaml_file(&slide_file(input))
+    }
+
+    /// JSON Schema describing `slide.yaml`, for editors to check slides while they're written
+    pub fn json_schema() -> String {
+        serde_json::to_string_pretty(&schema_for!(Slide)).expect("schemas always serialize")
     }
 
-    pub fn example() -> Self {
-        let example_keyset = HashSet::from(["key1".into(), "key2".into()]);
-        Self {
-            background_path: "path".into(),
-            nonclickables: vec![Nonclickable {
-                image_path: "path2".into(),
-                position: Coords {x: 0.0, y: 0.0},
-                anchor: Coords {x: 0.0, y: 0.0},
-                offset: Coords {x: 0, y: 0},
-            }],
-            clickables: vec![Clickable { 
-                image_path: "path3".into(), 
-                position: Coords {x: 0.0, y: 0.0}, 
-                anchor: Coords {x: 0.0, y: 0.0}, 
-                offset: Coords {x: 0, y: 0}, 
-                slide_path: "path4".into(), 
-                adds_keys: example_keyset.clone(), 
-                removes_keys: example_keyset.clone(), 
-                must_have_keys: example_keyset.clone(), 
-                mustnt_have_keys: example_keyset, 
-            }],
+    /// Every image the slide draws, along with what to show in its place if it's missing
+    pub fn images(&self) -> Vec<(&Path, FallbackAsset)> {
+        let mut images = vec![(self.background_path.as_path(), FallbackAsset::Background)];
+        for nonclickable in &self.nonclickables {
+            images.push((&nonclickable.image_path, FallbackAsset::Nonclickable));
         }
+        for clickable in &self.clickables {
+            if let Some(image_path) = &clickable.image_path {
+                images.push((image_path, FallbackAsset::Clickable));
+            }
+        }
+        images
     }
 }
 
 
 
 
-pub fn recursive_check_dir(path: PathBuf, slides_found: &mut HashSet<PathBuf>) {
-    if cfg!(feature="portable") {
+pub fn recursive_check_dir(path: PathBuf, slides_found: &mut HashSet<PathBuf>) -> Result<(), Error> {
+    if let Some(pack) = PACK.get() {
+        if !pack.is_dir(&path) {
+            return Err(Error::Missing { path, source: None })
+        }
+        let slides = pack.paths().filter(|file| file.starts_with(&path) && file.ends_with(YAML_FILENAME));
+        slides_found.extend(slides.map(PathBuf::from));
+
+    } else if cfg!(feature="portable") {
 
-        for entry in fs::read_dir(&path).expect(&format!("recursive check {path:?}")) {
-            match entry {
-                Ok(val) => {
-                    if val.file_name() == YAML_FILENAME {
-                        slides_found.insert(val.path());
-                    }
-                    if val.path().is_dir() { 
-                        recursive_check_dir(val.path(), slides_found);
-                    } 
-                },
-                Err(e) => {eprintln!("could not read directory entry {path:?}: {e}");},
+        let entries = fs::read_dir(&path).map_err(|e| Error::Missing { path: path.clone(), source: Some(e) })?;
+        for entry in entries {
+            let val = entry.map_err(|e| Error::Missing { path: path.clone(), source: Some(e) })?;
+            if val.file_name() == YAML_FILENAME {
+                slides_found.insert(val.path());
             }
+            if val.path().is_dir() { 
+                recursive_check_dir(val.path(), slides_found)?;
+            } 
         }
 
     } else {    // for static assets
         if ASSETS.contains(path.join(YAML_FILENAME)) {
             slides_found.insert(path.join(YAML_FILENAME));
         }
-        for dir in ASSETS.get_dir(&path).unwrap().dirs() {
-            recursive_check_dir(dir.path().into(), slides_found);
-            
+        let dir = ASSETS.get_dir(&path).ok_or(Error::Missing { path: path.clone(), source: None })?;
+        for dir in dir.dirs() {
+            recursive_check_dir(dir.path().into(), slides_found)?;
         }
     }
+    Ok(())
 }
 
 
-pub fn recursive_check_yaml(path: PathBuf, slides_visited: &mut HashSet<PathBuf>) {
-    
-    match Slide::read_yaml(&path) {
-        Ok(slide) => {
-            if !slides_visited.contains(&prefix_path(&path).join(YAML_FILENAME)) {
-                slides_visited.insert(prefix_path(&path).join(YAML_FILENAME));
-                read_image(canonical_join(&path, &slide.background_path), FallbackAsset::Background);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-                for nonclickable in slide.nonclickables {
-                    read_image(canonical_join(&path, &nonclickable.image_path), FallbackAsset::Nonclickable);
-                }
+    /// A story with `story/slide.yaml`, `story/Forest/slide.yaml` and `story/Forest/tree.png`
+    fn names_in_fake_story(directory: &Path) -> Option<Vec<OsString>> {
+        let names: &[&str] = match directory.to_str()? {
+            "" => &["story"],
+            "story" => &["slide.yaml", "Forest"],
+            "story/Forest" => &["slide.yaml", "tree.png"],
+            _ => return None,
+        };
+        Some(names.iter().map(OsString::from).collect())
+    }
 
-                for clickable in slide.clickables {
-                    recursive_check_yaml(canonical_join(&path, &clickable.slide_path), slides_visited);
-                    read_image(canonical_join(&path, &clickable.image_path), FallbackAsset::Clickable);
-                }
-            }
+    #[test]
+    fn paths_leaving_the_story_root_are_rejected() {
+        for path in ["story/../secret.txt", "story/forest/../../secret.txt", "/etc/passwd"] {
+            assert!(matches!(validate_path(Path::new(path)), Err(Error::PathEscape { .. })), "{path}");
         }
-        Err(e) => {eprintln!("{e}")}
-    };
+    }
+
+    #[test]
+    fn paths_that_come_back_inside_the_root_are_allowed() {
+        assert!(validate_path(Path::new("story/forest/../slide.yaml")).is_ok());
+        assert!(validate_path(&prefix_path(Path::new("/./slide.yaml"))).is_ok());
+    }
+
+    #[test]
+    fn dots_are_resolved_inside_the_root() {
+        assert_eq!(resolve_dots(Path::new("./story/forest/../tree.png")), Some("story/tree.png".into()));
+        assert_eq!(resolve_dots(Path::new("story/./forest/tree.png")), Some("story/forest/tree.png".into()));
+        assert_eq!(resolve_dots(Path::new("story/../tree.png")), None);
+    }
+
+    #[test]
+    fn cache_keys_name_each_image_once() {
+        assert_eq!(cache_key(Path::new("story/forest/../bg.png")), cache_key(Path::new("story/bg.png")));
+        assert_eq!(cache_key(Path::new("story/../bg.png")), Path::new("story/../bg.png"));
+    }
+
+    #[test]
+    fn huge_sprite_sheet_grids_are_clamped_to_the_image() {
+        let sheet = SpriteSheet { columns: 100_000, rows: 100_000, frame_count: None, fps: 10.0 };
+        let frames = slice_sprite_sheet(&RgbaImage::new(4, 2), &sheet);
+        assert_eq!(frames.len(), 8);
+        assert!(frames.iter().all(|frame| frame.image.dimensions() == (1, 1)));
+    }
 
-}
\ No newline at end of file
+    #[test]
+    fn unknown_fields_are_ignored_and_listed() {
+        let yaml = b"\
+background_path: bg.png
+backgorund_music: theme.ogg
+clickables:
+  - slide_path: forest
+    hotspot: !rect {from: {x: 0, y: 0}, to: {x: 1, y: 1, z: 1}}
+";
+        let (slide, unknown_fields): (Slide, _) = parse_yaml(Path::new("story/slide.yaml"), yaml).unwrap();
+        assert!(slide.clickables[0].hotspot.is_some());
+        assert_eq!(unknown_fields, ["backgorund_music", "clickables[0].hotspot.to.z"]);
+    }
+
+    #[test]
+    fn actual_case_finds_the_spelling_on_disk() {
+        let actual = |path: &str| actual_case_in(Path::new(path), names_in_fake_story);
+        assert_eq!(actual("story/Forest/tree.png"), Some("story/Forest/tree.png".into()));
+        assert_eq!(actual("story/forest/TREE.png"), Some("story/Forest/tree.png".into()));
+        assert_eq!(actual("story/forest/bush.png"), None);
+        assert_eq!(actual("story/slide.yaml/tree.png"), None);
+    }
+}
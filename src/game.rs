@@ -1,8 +1,11 @@
-use std::{collections::HashSet, fs, ops::{Add, Mul, Sub}, path::PathBuf};
+use std::{collections::HashSet, fs, ops::{Add, Mul, Sub}, path::{Path, PathBuf}};
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use crate::filesystem::{canonical_join, prefix_path, Slide, ASSETS};
+use crate::error::Error;
+use crate::filesystem::{canonical_join, prefix_path, slide_file, Slide, ASSETS};
+use crate::pack::{Pack, PACK};
 
 
 
@@ -15,37 +18,73 @@ pub struct Game {
     pub slide: Slide,
 }
 impl Game {
-    pub fn goto(&mut self, path: &PathBuf) -> Result<(), String> {
+    pub fn goto(&mut self, path: &Path) -> Result<(), Error> {
         let new_location = self.cd(path)?;
-        self.slide = Slide::read_yaml(&new_location)?;
+        self.slide = read_slide(&new_location)?;
         self.location = new_location;
         Ok(())
     }
-    fn cd(& self, path: &PathBuf) -> Result<PathBuf, String>{
+    /// Reads the current slide again, for when it changed on disk
+    pub fn reload(&mut self) -> Result<(), Error> {
+        self.slide = read_slide(&self.location)?;
+        Ok(())
+    }
+    fn cd(& self, path: &Path) -> Result<PathBuf, Error>{
         let try_path = canonical_join(&self.location, path);
-        if cfg!(feature="portable") {
-            match fs::read_dir(&prefix_path(&try_path)) {
-                Ok(_) => Ok(try_path),
-                Err(e) => Err(format!("could not find directory {try_path:?}: {e}")),
-            }
+        if let Some(pack) = PACK.get() {
+            directory_in_pack(pack, try_path)
+        } else if cfg!(feature="portable") {
+            let full_path = prefix_path(&try_path);
+            directory_in_folder(try_path, &full_path)
         } else {    //for static assets
-            let entry = ASSETS.get_entry(&prefix_path(&try_path))
-                .ok_or(format!("could not find static directory {try_path:?}"))?;
+            let entry = ASSETS.get_entry(prefix_path(&try_path))
+                .ok_or(Error::Missing { path: try_path.clone(), source: None })?;
             if let include_dir::DirEntry::Dir(_) = entry {
                 return Ok(try_path)
             }
-            Err(format!("static {path:?} is not a directory"))
+            Err(Error::NotADirectory { path: try_path })
         }
     }
 }
+
+/// Reads a slide to play, warning about fields that are ignored since `check` refuses them
+fn read_slide(location: &Path) -> Result<Slide, Error> {
+    let (slide, unknown_fields) = Slide::read_yaml_checked(location)?;
+    for field in unknown_fields {
+        log::warn!("ignoring unknown field {field:?} in {:?}", slide_file(location));
+    }
+    Ok(slide)
+}
+
+/// `try_path` if it's a folder in the pack
+fn directory_in_pack(pack: &Pack, try_path: PathBuf) -> Result<PathBuf, Error> {
+    let full_path = prefix_path(&try_path);
+    if pack.is_dir(&full_path) {
+        Ok(try_path)
+    } else if pack.file(&full_path).is_some() {
+        Err(Error::NotADirectory { path: try_path })
+    } else {
+        Err(Error::Missing { path: try_path, source: None })
+    }
+}
+
+/// `try_path` if `full_path`, where it is on disk, is a folder
+fn directory_in_folder(try_path: PathBuf, full_path: &Path) -> Result<PathBuf, Error> {
+    match fs::metadata(full_path) {
+        Ok(metadata) if metadata.is_dir() => Ok(try_path),
+        Ok(_) => Err(Error::NotADirectory { path: try_path }),
+        Err(e) => Err(Error::Missing { path: try_path, source: Some(e) }),
+    }
+}
+
 impl TryFrom<SaveFile> for Game {
-    type Error = String;
+    type Error = Error;
 
     fn try_from(value: SaveFile) -> Result<Self, Self::Error> {
         Ok(Game {
             location: value.location.clone(),
             keys: value.keys,
-            slide: Slide::read_yaml(&value.location)?,
+            slide: read_slide(&value.location)?,
         })
     }
 }
@@ -75,7 +114,8 @@ impl Default for SaveFile {
 
 
 
-#[derive(Serialize, Deserialize, Debug, Default)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Default)]
+#[schemars(deny_unknown_fields)]
 pub struct Nonclickable {
     pub image_path: PathBuf,
     #[serde(default)]
@@ -84,15 +124,37 @@ pub struct Nonclickable {
     pub anchor: Coords<f32>,
     #[serde(default)]
     pub offset: Coords<i32>,
-    //delay: f32,
+    #[serde(default)]
+    pub sprite_sheet: Option<SpriteSheet>,
+    /// Seconds after the slide loads before the element appears
+    #[serde(default)]
+    pub delay: f32,
+    /// Seconds the element stays on screen, forever if unset
+    #[serde(default)]
+    pub duration: Option<f32>,
+    /// Seconds spent fading in once the element appears
+    #[serde(default)]
+    pub fade_in: f32,
 }
 
 
-#[derive(Serialize, Deserialize, Debug, Default)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Default)]
+#[schemars(deny_unknown_fields)]
 pub struct Clickable {
-    pub image_path: PathBuf,
+    #[serde(default)]
+    pub image_path: Option<PathBuf>,
     pub slide_path: PathBuf,
     #[serde(default)]
+    pub hotkey: Option<String>,
+    #[serde(default)]
+    #[schemars(with = "Option<HotspotSchema>")]
+    pub hotspot: Option<Hotspot>,
+    #[serde(default)]
+    pub transition: Option<Transition>,
+    /// OGG or WAV played once when the clickable is activated
+    #[serde(default)]
+    pub sound: Option<PathBuf>,
+    #[serde(default)]
     pub position: Coords<f32>,
     #[serde(default)]
     pub anchor: Coords<f32>,
@@ -106,11 +168,154 @@ pub struct Clickable {
     pub must_have_keys: HashSet<String>,
     #[serde(default)]
     pub mustnt_have_keys: HashSet<String>,
+    #[serde(default)]
+    pub sprite_sheet: Option<SpriteSheet>,
+    #[serde(default)]
+    pub delay: f32,
+    #[serde(default)]
+    pub duration: Option<f32>,
+    #[serde(default)]
+    pub fade_in: f32,
+}
+impl Clickable {
+    /// Whether the player's keys let them see and use this clickable
+    pub fn is_available(&self, keys: &HashSet<String>) -> bool {
+        self.must_have_keys.is_subset(keys) && self.mustnt_have_keys.is_disjoint(keys)
+    }
+}
+
+
+
+/// Goes to another slide on its own after some time, 
+/// making the slide a timed choice if it also has clickables
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+#[schemars(deny_unknown_fields)]
+pub struct AutoAdvance {
+    pub after_ms: u64,
+    pub slide_path: PathBuf,
+    #[serde(default)]
+    pub adds_keys: HashSet<String>,
+    #[serde(default)]
+    pub removes_keys: HashSet<String>,
+    #[serde(default)]
+    pub transition: Option<Transition>,
+    /// Draws a bar along the bottom of the slide showing the time left
+    #[serde(default)]
+    pub show_timer: bool,
+}
+
+
+
+/// Animation frames laid out in a grid, read left to right and top to bottom
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy)]
+#[schemars(deny_unknown_fields)]
+pub struct SpriteSheet {
+    pub columns: u32,
+    pub rows: u32,
+    /// Only the first this many frames are used, all of them if unset
+    #[serde(default)]
+    pub frame_count: Option<u32>,
+    pub fps: f32,
+}
+impl SpriteSheet {
+    /// Columns and rows the sheet is actually cut into, at least one and no more than the image has pixels
+    pub fn grid(&self, image_width: u32, image_height: u32) -> (u32, u32) {
+        (self.columns.clamp(1, image_width.max(1)), self.rows.clamp(1, image_height.max(1)))
+    }
 }
 
 
 
-#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy)]
+/// A clickable region that doesn't need an image, overriding the image derived hitbox when present
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum Hotspot {
+    Rect {
+        from: Coords<f32>,
+        to: Coords<f32>,
+        #[serde(default)]
+        units: Units,
+    },
+    Polygon {
+        points: Vec<Coords<f32>>,
+        #[serde(default)]
+        units: Units,
+    },
+}
+
+/// A clickable region written as `!rect` or `!polygon` followed by its fields
+//  JSON Schema can't describe YAML tags, so `Hotspot`'s schema only checks the fields after them
+#[derive(JsonSchema)]
+#[serde(untagged, deny_unknown_fields)]
+#[schemars(rename = "Hotspot")]
+#[allow(dead_code)]
+enum HotspotSchema {
+    Rect {
+        from: Coords<f32>,
+        to: Coords<f32>,
+        #[serde(default)]
+        units: Units,
+    },
+    Polygon {
+        points: Vec<Coords<f32>>,
+        #[serde(default)]
+        units: Units,
+    },
+}
+
+/// Whether hotspot coordinates are fractions of the background size or pixels
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Units {
+    #[default]
+    Relative,
+    Pixels,
+}
+
+
+
+/// How the screen changes over to the next slide
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy)]
+#[schemars(deny_unknown_fields)]
+pub struct Transition {
+    pub kind: TransitionKind,
+    #[serde(default)]
+    pub direction: Direction,
+    #[serde(default = "Transition::default_duration_ms")]
+    pub duration_ms: u64,
+}
+impl Transition {
+    fn default_duration_ms() -> u64 {
+        500
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum TransitionKind {
+    FadeToBlack,
+    Crossfade,
+    /// The new slide is revealed behind an edge moving in `direction`
+    Wipe,
+    /// The new slide pushes the old one out in `direction`
+    Slide,
+    Dissolve,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Default, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    #[default]
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Default, Clone, Copy)]
+#[schemars(deny_unknown_fields)]
 pub struct Coords<T> {
     pub x: T,
     pub y: T,
@@ -159,3 +364,32 @@ impl<T: Copy> Coords<T> {
 
 
 
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pack_folders_files_and_missing_paths() {
+        let pack: Pack = [
+            (prefix_path(Path::new("/slide.yaml")), Vec::new()),
+            (prefix_path(Path::new("/forest/slide.yaml")), Vec::new()),
+        ].into_iter().collect();
+        assert!(matches!(directory_in_pack(&pack, "/forest".into()), Ok(path) if path == Path::new("/forest")));
+        assert!(matches!(directory_in_pack(&pack, "/forest/slide.yaml".into()), Err(Error::NotADirectory { .. })));
+        assert!(matches!(directory_in_pack(&pack, "/cave".into()), Err(Error::Missing { .. })));
+    }
+
+    #[test]
+    fn disk_folders_files_and_missing_paths() {
+        let directory = std::env::temp_dir().join(format!("ferrous-tale-cd-{}", std::process::id()));
+        fs::create_dir_all(directory.join("forest")).unwrap();
+        fs::write(directory.join("slide.yaml"), "").unwrap();
+
+        assert!(directory_in_folder("/forest".into(), &directory.join("forest")).is_ok());
+        assert!(matches!(directory_in_folder("/slide.yaml".into(), &directory.join("slide.yaml")), Err(Error::NotADirectory { .. })));
+        assert!(matches!(directory_in_folder("/cave".into(), &directory.join("cave")), Err(Error::Missing { .. })));
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
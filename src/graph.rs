@@ -0,0 +1,233 @@
+use std::{collections::{BTreeSet, HashSet}, fmt::Write, path::PathBuf};
+
+use clap::ValueEnum;
+
+use crate::filesystem::{canonical_join, Slide};
+
+
+// Written by soweli Luna
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum GraphFormat {
+    /// Graphviz, render with `dot -Tsvg`
+    Dot,
+    /// Mermaid flowchart, renders in markdown on most forges
+    Mermaid,
+}
+
+
+/// How the player gets from one slide to another
+pub enum EdgeKind {
+    Click,
+    AutoAdvance { after_ms: u64 },
+}
+
+pub struct Edge {
+    pub from: PathBuf,
+    pub to: PathBuf,
+    pub kind: EdgeKind,
+    pub adds_keys: HashSet<String>,
+    pub removes_keys: HashSet<String>,
+    pub must_have_keys: HashSet<String>,
+    pub mustnt_have_keys: HashSet<String>,
+}
+impl Edge {
+    /// Whether a player holding `keys` can take this edge
+    pub fn is_available(&self, keys: &BTreeSet<String>) -> bool {
+        self.must_have_keys.iter().all(|key| keys.contains(key))
+            && !self.mustnt_have_keys.iter().any(|key| keys.contains(key))
+    }
+
+    /// Short description of the edge, one line per kind of key change
+    fn label(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        if let EdgeKind::AutoAdvance { after_ms } = self.kind {
+            lines.push(format!("after {after_ms}ms"));
+        }
+        for (prefix, keys) in [
+            ("needs", &self.must_have_keys),
+            ("needs not", &self.mustnt_have_keys),
+            ("+", &self.adds_keys),
+            ("-", &self.removes_keys),
+        ] {
+            if !keys.is_empty() {
+                let mut keys: Vec<&str> = keys.iter().map(String::as_str).collect();
+                keys.sort();
+                lines.push(format!("{prefix} {}", keys.join(", ")));
+            }
+        }
+        lines
+    }
+}
+
+
+/// Every slide reachable from the start, and every way to move between them
+pub struct StoryGraph {
+    /// Slide locations, sorted so the output is stable between runs
+    pub slides: BTreeSet<PathBuf>,
+    /// Locations whose `slide.yaml` could not be read, left for `check` to explain
+    pub broken: BTreeSet<PathBuf>,
+    pub edges: Vec<Edge>,
+}
+impl StoryGraph {
+    pub fn build(start: PathBuf) -> Self {
+        let mut graph = Self {
+            slides: BTreeSet::new(),
+            broken: BTreeSet::new(),
+            edges: Vec::new(),
+        };
+        let mut to_visit = vec![start];
+
+        while let Some(location) = to_visit.pop() {
+            if graph.slides.contains(&location) || graph.broken.contains(&location) {
+                continue
+            }
+            let Ok(slide) = Slide::read_yaml(&location) else {
+                graph.broken.insert(location);
+                continue
+            };
+
+            for clickable in slide.clickables {
+                let to = canonical_join(&location, &clickable.slide_path);
+                to_visit.push(to.clone());
+                graph.edges.push(Edge {
+                    from: location.clone(),
+                    to,
+                    kind: EdgeKind::Click,
+                    adds_keys: clickable.adds_keys,
+                    removes_keys: clickable.removes_keys,
+                    must_have_keys: clickable.must_have_keys,
+                    mustnt_have_keys: clickable.mustnt_have_keys,
+                });
+            }
+            if let Some(auto_advance) = slide.auto_advance {
+                let to = canonical_join(&location, &auto_advance.slide_path);
+                to_visit.push(to.clone());
+                graph.edges.push(Edge {
+                    from: location.clone(),
+                    to,
+                    kind: EdgeKind::AutoAdvance { after_ms: auto_advance.after_ms },
+                    adds_keys: auto_advance.adds_keys,
+                    removes_keys: auto_advance.removes_keys,
+                    must_have_keys: HashSet::new(),
+                    mustnt_have_keys: HashSet::new(),
+                });
+            }
+            graph.slides.insert(location);
+        }
+        graph
+    }
+
+    pub fn render(&self, format: GraphFormat) -> String {
+        match format {
+            GraphFormat::Dot => self.to_dot(),
+            GraphFormat::Mermaid => self.to_mermaid(),
+        }
+    }
+
+    pub fn to_dot(&self) -> String {
+        let escape = |text: &str| text.replace('\\', "\\\\").replace('"', "\\\"");
+        let quote = |text: &str| format!("\"{}\"", escape(text));
+
+        let mut dot = String::from("digraph story {\n    node [shape=box];\n");
+        for slide in &self.slides {
+            _ = writeln!(dot, "    {};", quote(&slide.to_string_lossy()));
+        }
+        for slide in &self.broken {
+            _ = writeln!(dot, "    {} [color=red, style=dashed];", quote(&slide.to_string_lossy()));
+        }
+        for edge in &self.edges {
+            let style = match edge.kind {
+                EdgeKind::Click => "",
+                EdgeKind::AutoAdvance { .. } => ", style=dashed",
+            };
+            // escaped before joining, so the line breaks stay as `\n` for graphviz
+            let label: Vec<String> = edge.label().iter().map(|line| escape(line)).collect();
+            _ = writeln!(dot, "    {} -> {} [label=\"{}\"{style}];",
+                quote(&edge.from.to_string_lossy()),
+                quote(&edge.to.to_string_lossy()),
+                label.join("\\n"),
+            );
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    pub fn to_mermaid(&self) -> String {
+        // mermaid ids can't contain slashes, so slides are numbered and labelled with their path
+        let ids: Vec<&PathBuf> = self.slides.iter().chain(&self.broken).collect();
+        let id = |slide: &PathBuf| ids.iter().position(|other| *other == slide).unwrap_or(0);
+        let quote = |text: &str| format!("\"{}\"", text.replace('"', "#quot;"));
+
+        let mut mermaid = String::from("flowchart TD\n");
+        for (index, slide) in ids.iter().enumerate() {
+            _ = writeln!(mermaid, "    s{index}[{}]", quote(&slide.to_string_lossy()));
+        }
+        for slide in &self.broken {
+            _ = writeln!(mermaid, "    style s{} stroke:#f00,stroke-dasharray:4", id(slide));
+        }
+        for edge in &self.edges {
+            let arrow = match edge.kind {
+                EdgeKind::Click => "-->",
+                EdgeKind::AutoAdvance { .. } => "-.->",
+            };
+            let label = edge.label();
+            if label.is_empty() {
+                _ = writeln!(mermaid, "    s{} {arrow} s{}", id(&edge.from), id(&edge.to));
+            } else {
+                _ = writeln!(mermaid, "    s{} {arrow}|{}| s{}", id(&edge.from), quote(&label.join("<br>")), id(&edge.to));
+            }
+        }
+        mermaid
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The root leads to a vault that needs a key with a quote in its name, and the vault leads back on its own
+    fn two_slides() -> StoryGraph {
+        let keys = |keys: &[&str]| keys.iter().map(|key| key.to_string()).collect();
+        let edge = |from: &str, to: &str, kind, must_have: &[&str], adds: &[&str]| Edge {
+            from: from.into(),
+            to: to.into(),
+            kind,
+            adds_keys: keys(adds),
+            removes_keys: HashSet::new(),
+            must_have_keys: keys(must_have),
+            mustnt_have_keys: HashSet::new(),
+        };
+        StoryGraph {
+            slides: ["/".into(), "/vault".into()].into(),
+            broken: BTreeSet::new(),
+            edges: vec![
+                edge("/", "/vault", EdgeKind::Click, &["say \"open\""], &["gold"]),
+                edge("/vault", "/", EdgeKind::AutoAdvance { after_ms: 500 }, &[], &[]),
+            ],
+        }
+    }
+
+    #[test]
+    fn dot_escapes_quotes_and_keeps_line_breaks() {
+        assert_eq!(two_slides().to_dot(), r#"digraph story {
+    node [shape=box];
+    "/";
+    "/vault";
+    "/" -> "/vault" [label="needs say \"open\"\n+ gold"];
+    "/vault" -> "/" [label="after 500ms", style=dashed];
+}
+"#);
+    }
+
+    #[test]
+    fn mermaid_escapes_quotes_as_entities() {
+        assert_eq!(two_slides().to_mermaid(), r#"flowchart TD
+    s0["/"]
+    s1["/vault"]
+    s0 -->|"needs say #quot;open#quot;<br>+ gold"| s1
+    s1 -.->|"after 500ms"| s0
+"#);
+    }
+}
@@ -0,0 +1,37 @@
+//! The FerrousTale engine, shared by the game and its benchmarks
+
+use std::path::PathBuf;
+
+use console::ConsoleCommand;
+
+pub mod game;
+pub mod draw;
+pub mod filesystem;
+pub mod cache;
+pub mod error;
+pub mod transition;
+pub mod audio;
+pub mod settings;
+pub mod text;
+pub mod report;
+pub mod logging;
+pub mod graph;
+pub mod check;
+pub mod watch;
+pub mod overlay;
+pub mod console;
+pub mod manifest;
+pub mod scaffold;
+pub mod pack;
+
+
+// Written by soweli Luna
+
+/// Sent to the event loop from other threads
+#[derive(Debug)]
+pub enum UserEvent {
+    /// Files under the story folder were added, removed or modified, only sent with `play --dev`
+    StoryChanged(Vec<PathBuf>),
+    /// Typed into the developer console, only sent with `play --dev`
+    Console(ConsoleCommand),
+}
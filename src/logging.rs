@@ -0,0 +1,91 @@
+use std::{fs::{File, OpenOptions}, io::Write, path::{Path, PathBuf}, sync::{LazyLock, Mutex, OnceLock}, time::{SystemTime, UNIX_EPOCH}};
+
+use log::{LevelFilter, Log, Metadata, Record};
+
+use crate::game::Game;
+
+
+// Written by soweli Luna
+
+/// Where the log goes when no `--log-file` is given,
+/// since the console is hidden on Windows
+const DEFAULT_LOG_PATH: &str = "ferrous-tale.log";
+
+/// Where the player is in the story, attached to every log line so bug reports can be retraced
+static CONTEXT: LazyLock<Mutex<String>> = LazyLock::new(|| Mutex::new(String::new()));
+
+
+/// Sets up the `log` macros, `verbosity` being how many times `--verbose` was passed
+pub fn init(verbosity: u8, log_file: Option<&Path>) {
+    let level = match verbosity {
+        0 => LevelFilter::Warn,
+        1 => LevelFilter::Info,
+        2 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    };
+    let logger = Logger {
+        path: log_file.unwrap_or(Path::new(DEFAULT_LOG_PATH)).into(),
+        file: OnceLock::new(),
+    };
+    if log::set_boxed_logger(Box::new(logger)).is_ok() {
+        log::set_max_level(level);
+    }
+}
+
+/// Records the player's location and keys for the log lines that follow
+pub fn set_context(game: &Game) {
+    let mut keys: Vec<&str> = game.keys.iter().map(String::as_str).collect();
+    keys.sort();
+    *CONTEXT.lock().unwrap() = format!("{} [{}]", game.location.display(), keys.join(", "));
+}
+
+
+/// Writes every record to stderr and the log file
+struct Logger {
+    path: PathBuf,
+    /// Only opened once there's something to write, so quiet runs don't leave empty logs around
+    file: OnceLock<Option<Mutex<File>>>,
+}
+impl Logger {
+    fn file(&self) -> Option<&Mutex<File>> {
+        self.file.get_or_init(|| {
+            match OpenOptions::new().create(true).append(true).open(&self.path) {
+                Ok(file) => Some(Mutex::new(file)),
+                Err(e) => {
+                    eprintln!("could not open log file {:?}: {e}", self.path);
+                    None
+                }
+            }
+        }).as_ref()
+    }
+}
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return
+        }
+        let context = CONTEXT.lock().unwrap().clone();
+        let line = if context.is_empty() {
+            format!("{:<5} {}", record.level(), record.args())
+        } else {
+            format!("{:<5} {context}: {}", record.level(), record.args())
+        };
+        eprintln!("{line}");
+
+        if let Some(file) = self.file() {
+            let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+            // a failing log file has nowhere else to be reported
+            _ = writeln!(file.lock().unwrap(), "[{timestamp}] {line}");
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(Some(file)) = self.file.get() {
+            _ = file.lock().unwrap().flush();
+        }
+    }
+}
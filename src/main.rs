@@ -1,38 +1,123 @@
-#![windows_subsystem = "windows"]
+// release builds don't open a console window next to the game on Windows,
+// debug builds keep theirs so the developer console and logs have somewhere to go
+#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 use std::collections::HashSet;
 use std::fs;
 use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::Ordering;
 use std::rc::Rc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use clap::Parser;
-use winit::event::{ElementState, Event, MouseButton, WindowEvent};
-use winit::event_loop::{ControlFlow, EventLoop};
-use winit::window::WindowBuilder;
+use clap::{Parser, Subcommand};
+use winit::event::{ElementState, Event, KeyEvent, MouseButton, StartCause, WindowEvent};
+use winit::event_loop::{ControlFlow, EventLoopBuilder};
+use winit::dpi::PhysicalSize;
+use winit::window::{Fullscreen, Window, WindowBuilder};
 use serde_yaml as yaml;
 
-mod game;
-mod draw;
-mod filesystem;
+use ferrous_tale::{audio, cache, check, console, draw, filesystem, game, graph, logging, manifest, overlay, pack, report, scaffold, settings, transition, watch, UserEvent};
 use game::{Game, Coords, SaveFile};
-use draw::Canvas;
-use filesystem::{prefix_path, Slide};
+use draw::{hotkey_matches, Action, Canvas, Viewport, FRAME_TIME};
+use transition::TransitionState;
+use audio::Audio;
+use settings::{Scaling, Settings, SettingsAction, SettingsScreen};
+use report::{report_error, ERRORS};
+use graph::{GraphFormat, StoryGraph};
+use check::{CheckFormat, Severity};
+use cache::IMAGE_CACHE;
+use console::ConsoleCommand;
+use manifest::Manifest;
+use filesystem::Slide;
 
 
 const SAVE_FILE_PATH: &str = "save.yaml";
 
+
 /// FerrousTale, a simple slide based interactive story game engine
 //  Written by soweli Luna
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
-    /// Generate an example slide YAML
-    #[arg(short, long)]
-    example: bool,
+    /// What to do, plays the story if left out
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Refuse to load assets whose paths leave the story folder or differ in case from the file
+    #[arg(long, global = true)]
+    strict: bool,
+
+    /// Log more detail, repeat for even more (-v info, -vv debug, -vvv trace)
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Write the log here instead of ferrous-tale.log
+    #[arg(long, global = true, value_name = "PATH")]
+    log_file: Option<PathBuf>,
 
+    /// Read the story from this pack. Playing uses story.ftpack on its own if it's in the working directory and there's no story folder
+    #[arg(long, global = true, value_name = "PATH")]
+    pack: Option<PathBuf>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Play the story
+    Play {
+        /// Reload the slide whenever its files change (needs the portable feature), 
+        /// allow the debug overlay, and read developer console commands from stdin 
+        /// (on Windows only debug builds have a console to type them into)
+        #[arg(long)]
+        dev: bool,
+    },
     /// Recursively search the story tree for issues
-    #[arg(short, long)]
-    check: bool,
+    Check {
+        /// How the issues are printed
+        #[arg(long, value_enum, default_value_t = CheckFormat::Human)]
+        format: CheckFormat,
+    },
+    /// Create a small playable story to start from
+    New {
+        /// Folder to create the story in, the story goes in its `story/` folder
+        directory: PathBuf,
+    },
+    /// Save a slide as a PNG without opening a window
+    Render {
+        /// The slide to render, relative to the story root
+        #[arg(default_value = "/")]
+        slide: PathBuf,
+        /// Where to save the image
+        #[arg(short, long, default_value = "slide.png")]
+        output: PathBuf,
+        /// Keys the player has, which decide what clickables are shown
+        #[arg(short, long, value_delimiter = ',')]
+        keys: Vec<String>,
+        /// How many seconds after the slide loaded to render it, for elements that are delayed or fade in
+        #[arg(long, default_value = "0", value_parser = parse_seconds)]
+        at: Duration,
+    },
+    /// Print the slide graph, with the keys each choice needs and changes
+    Graph {
+        #[arg(value_enum, default_value_t = GraphFormat::Dot)]
+        format: GraphFormat,
+    },
+    /// Check the story and bundle the files its slides use into a single pack to distribute
+    Pack {
+        /// Where to write the pack
+        #[arg(short, long, default_value = pack::DEFAULT_PACK_PATH)]
+        output: PathBuf,
+        /// Encode PNGs again as small as possible, only keeping the result when it's smaller
+        #[arg(long)]
+        recompress: bool,
+    },
+    /// Print a JSON Schema for slide.yaml, so editors can point out mistakes while writing slides
+    Schema {
+        /// Write the schema here instead of printing it
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
 }
 
 
@@ -40,33 +125,117 @@ struct Args {
 
 fn main() {
     let args = Args::parse();
-
-    if args.example {
-        fs::write("example_slide.yaml", 
-            serde_yaml::to_string(&Slide::example()).unwrap()
-        ).unwrap();
+    logging::init(args.verbose, args.log_file.as_deref());
+    filesystem::STRICT_PATHS.store(args.strict, Ordering::Relaxed);
+
+    let command = args.command.unwrap_or(Command::Play { dev: false });
+    // packing reads the story folder, so it shouldn't be pointed at a previous pack,
+    // and the author tools only look at a pack when asked to
+    if !matches!(command, Command::New { .. } | Command::Pack { .. } | Command::Schema { .. }) {
+        if let Err(e) = pack::load(args.pack.as_deref(), matches!(command, Command::Play { .. })) {
+            // carrying on with a different story than the one asked for would pass checks it shouldn't
+            if args.pack.is_some() {
+                eprintln!("could not load the pack: {e}");
+                process::exit(1);
+            }
+            report_error(format!("could not load {}, using the story folder: {e}", pack::DEFAULT_PACK_PATH));
+        }
     }
 
-    if args.check {
-        let mut dir_slides_found = HashSet::new();
-        filesystem::recursive_check_dir(prefix_path(&SaveFile::default().location), &mut dir_slides_found);
-        let mut yaml_slides_visited = HashSet::new();
-        filesystem::recursive_check_yaml(SaveFile::default().location, &mut yaml_slides_visited);
-        for slide in dir_slides_found.difference(&yaml_slides_visited) {
-            eprintln!("unreachable slide {slide:?}")
+    match command {
+        Command::Play { dev } => play(dev),
+        Command::Check { format } => {
+            let diagnostics = check::check_story(SaveFile::default().location);
+            check::print_diagnostics(&diagnostics, format);
+            if diagnostics.iter().any(|diagnostic| diagnostic.severity == Severity::Error) {
+                process::exit(1);
+            }
+        }
+        Command::New { directory } => {
+            let title = directory.file_name().map_or("New story".into(), |name| name.to_string_lossy());
+            match scaffold::new_story(&directory, &title) {
+                Ok(()) => println!("created a new story in {directory:?}, run it from there with a portable build"),
+                Err(e) => {
+                    eprintln!("{e}");
+                    process::exit(1);
+                }
+            }
+        }
+        Command::Render { slide, output, keys, at } => {
+            if let Err(e) = render(&slide, &output, keys, at) {
+                eprintln!("could not render {slide:?}: {e}");
+                process::exit(1);
+            }
+        }
+        Command::Graph { format } => {
+            print!("{}", StoryGraph::build(SaveFile::default().location).render(format));
+        }
+        Command::Schema { output: None } => println!("{}", Slide::json_schema()),
+        Command::Schema { output: Some(output) } => {
+            if let Err(e) = fs::write(&output, Slide::json_schema()) {
+                eprintln!("could not write {output:?}: {e}");
+                process::exit(1);
+            }
+        }
+        Command::Pack { output, recompress } => {
+            let start = SaveFile::default().location;
+            let diagnostics = check::check_story(start.clone());
+            if !diagnostics.is_empty() {
+                check::print_diagnostics(&diagnostics, CheckFormat::Human);
+            }
+            if diagnostics.iter().any(|diagnostic| diagnostic.severity == Severity::Error) {
+                eprintln!("not packing a story with errors");
+                process::exit(1);
+            }
+            match pack::pack_story(&start, &output, recompress) {
+                Ok(summary) => {
+                    println!("packed {} files, {} KiB, into {output:?}", summary.files, summary.bytes / 1024);
+                    if recompress {
+                        println!("recompressing saved {} KiB", summary.saved_bytes / 1024);
+                    }
+                }
+                Err(e) => {
+                    eprintln!("could not pack the story: {e}");
+                    process::exit(1);
+                }
+            }
         }
-        return
     }
+}
 
+/// Renders a slide the way a player with `keys` would see it `at` seconds after it loaded
+fn render(slide: &Path, output: &Path, keys: Vec<String>, at: Duration) -> Result<(), Box<dyn std::error::Error>> {
+    let mut game: Game = SaveFile::default().try_into()?;
+    game.goto(slide)?;
+    game.keys = keys.into_iter().collect();
 
+    let mut canvas = Canvas::build(&game, &Settings::default());
+    if !canvas.seek(at) {
+        return Err(format!("can't render {at:?} in, that's further back than the system clock goes").into())
+    }
+    console::save_screenshot(&canvas, output)?;
+    Ok(())
+}
 
-    let event_loop = EventLoop::new().unwrap();
-    let window = Rc::new(
-        WindowBuilder::new()
-        .with_resizable(false)
-        .with_title("FerrousTale")
-        .build(&event_loop)
-        .unwrap());
+/// A number of seconds for `--at`, which has to be finite and not negative
+fn parse_seconds(text: &str) -> Result<Duration, String> {
+    let seconds: f32 = text.parse().map_err(|e| format!("{e}"))?;
+    Duration::try_from_secs_f32(seconds).map_err(|_| "must be a finite number of seconds, zero or more".into())
+}
+
+fn play(dev: bool) {
+    let mut settings = Settings::load();
+    ERRORS.lock().unwrap().banner_enabled = settings.error_banner;
+
+    let event_loop = EventLoopBuilder::<UserEvent>::with_user_event().build().unwrap();
+    let mut window_builder = WindowBuilder::new()
+        .with_resizable(settings.scaling != Scaling::None)
+        .with_fullscreen(settings.fullscreen.then_some(Fullscreen::Borderless(None)))
+        .with_title(Manifest::load().title);
+    if let (Some(size), true) = (settings.window_size, settings.scaling != Scaling::None) {
+        window_builder = window_builder.with_inner_size(PhysicalSize::new(size.x, size.y));
+    }
+    let window = Rc::new(window_builder.build(&event_loop).unwrap());
     let context = softbuffer::Context::new(window.clone()).unwrap();
     let mut surface = softbuffer::Surface::new(&context, window.clone()).unwrap();
 
@@ -77,33 +246,87 @@ fn main() {
             match yaml::from_str(val) {
                 Ok(val) => val,
                 Err(e) => {
-                    eprintln!("could not deserialize save file: {e}");
+                    report_error(format!("could not deserialize save file: {e}"));
                     SaveFile::default()
                 }
             }
         }
         Err(e) => {
-            eprintln!("could not read save file: {e}");
+            log::info!("could not read save file, starting a new game: {e}");
             SaveFile::default()
         }
     };
 
-    let mut game: Game = save_file.try_into().expect("root initialization");
+    let mut game: Game = match save_file.try_into() {
+        Ok(game) => game,
+        Err(e) => {
+            // the slide the save points to may have been moved or removed since
+            report_error(format!("could not load saved slide, starting over: {e}"));
+            SaveFile::default().try_into().expect("root initialization")
+        }
+    };
+    logging::set_context(&game);
+    log::info!("starting");
+
 
+    let mut canvas = Canvas::build(&game, &settings);
+    filesystem::preload_reachable(&game.location, &game.slide);
 
-    let mut canvas = Canvas::build(&game);
+    let mut audio = Audio::new();
+    audio.set_music(&game.location, game.slide.music.as_ref());
 
     let mut mouse_pos = Coords {x: 0, y: 0};
 
+    let mut transition: Option<TransitionState> = None;
+    let mut transition_frame = Vec::new();
+    let mut next_frame = Instant::now();
+
+    let mut settings_screen = SettingsScreen::default();
+
+    // authors get the overlay, players shouldn't be able to peek at hidden clickables
+    let debug_overlay_allowed = dev || cfg!(debug_assertions);
+    let mut debug_overlay = false;
+
+    if dev {
+        console::spawn_console(event_loop.create_proxy());
+        if pack::PACK.get().is_some() {
+            log::warn!("play --dev can't watch a pack for changes, play the story folder to reload slides");
+        } else if cfg!(feature="portable") {
+            watch::watch_story(event_loop.create_proxy());
+        } else {
+            log::warn!("play --dev needs the portable feature, the story is built into the executable");
+        }
+    }
     
 
     // ~~~~~~~~~~~~~~~~ ^^^^ ~~~~~~~~~~~~~~~~ INIT CODE ~~~~~~~~~~~~~~~~ ^^^^ ~~~~~~~~~~~~~~~~ 
 
 
     event_loop.run(move |event, elwt| {
-        elwt.set_control_flow(ControlFlow::Wait);
-
         match event {
+            Event::AboutToWait => {
+                // nothing on the slide moves on while the player is in the settings
+                canvas.set_paused(settings_screen.open);
+                // transitions redraw every frame, which keeps the canvas updated as well
+                let deadline = if transition.is_some() { Some(next_frame) } else { canvas.next_update() };
+                let deadline = deadline.into_iter()
+                    .chain(audio.next_update())
+                    .chain(ERRORS.lock().unwrap().next_update())
+                    .min();
+                match deadline {
+                    Some(deadline) => elwt.set_control_flow(ControlFlow::WaitUntil(deadline)),
+                    None => elwt.set_control_flow(ControlFlow::Wait),
+                }
+            }
+            Event::NewEvents(StartCause::ResumeTimeReached { .. }) => {
+                audio.update();
+                if transition.is_none() && !settings_screen.open {
+                    if let Some(action) = canvas.timed_out() {
+                        follow_button(&mut game, &mut canvas, &mut transition, &mut audio, &settings, &window, action);
+                    }
+                }
+                window.request_redraw();
+            }
             Event::WindowEvent { window_id, event: WindowEvent::RedrawRequested } if window_id == window.id() => {
                 let (width, height) = {
                     let size = window.inner_size();
@@ -120,19 +343,48 @@ fn main() {
                 // ~~~~~~~~~~~~~~~~ vvvv ~~~~~~~~~~~~~~~~ REDRAW CODE ~~~~~~~~~~~~~~~~ vvvv ~~~~~~~~~~~~~~~~ 
 
                 let mut buffer = surface.buffer_mut().unwrap();
+                let buffer_age = buffer.age();
                 
+                canvas.update();
+                let banner_visible = ERRORS.lock().unwrap().update();
+                let viewport = Viewport::new(settings.scaling, canvas.size, width, height);
+                let damage = match &transition {
+                    Some(state) if !state.is_finished() => {
+                        state.draw(&canvas, &mut transition_frame);
+                        viewport.present(&transition_frame, canvas.size, &mut buffer, width, height);
+                        canvas.mark_all_dirty();
+                        next_frame = Instant::now() + FRAME_TIME;
+                        Vec::new()
+                    }
+                    _ if !viewport.is_identity() || settings_screen.open || banner_visible || debug_overlay => {
+                        transition = None;
+                        viewport.present(canvas.framebuffer(), canvas.size, &mut buffer, width, height);
+                        canvas.mark_all_dirty();
+                        Vec::new()
+                    }
+                    _ => {
+                        transition = None;
+                        canvas.draw_to_buffer(&mut buffer, buffer_age, width, height)
+                    }
+                };
 
+                if debug_overlay {
+                    overlay::draw_debug_overlay(&game, &canvas, &viewport, &mut buffer, width, height);
+                }
+                if settings_screen.open {
+                    settings_screen.draw(&settings, &mut buffer, width, height);
+                }
+                ERRORS.lock().unwrap().draw(&mut buffer, width, height);
 
-                //dbg!(&game.location);
-                //dbg!(&game.keys);
-
-                
-                canvas.draw_to_buffer(&mut buffer, width, height);
-
-                let canvas_size = canvas.size();
-                _ = window.request_inner_size(canvas_size);
+                if settings.scaling == Scaling::None && !settings.fullscreen {
+                    _ = window.request_inner_size(canvas.size());
+                }
 
-                buffer.present().unwrap();
+                if damage.is_empty() {
+                    buffer.present().unwrap();
+                } else {
+                    buffer.present_with_damage(&damage).unwrap();
+                }
 
 
                 // ~~~~~~~~~~~~~~~~ ^^^^ ~~~~~~~~~~~~~~~~ REDRAW CODE ~~~~~~~~~~~~~~~~ ^^^^ ~~~~~~~~~~~~~~~~ 
@@ -149,34 +401,75 @@ fn main() {
                 
                 // ~~~~~~~~~~~~~~~~ vvvv ~~~~~~~~~~~~~~~~ INPUT CODE ~~~~~~~~~~~~~~~~ vvvv ~~~~~~~~~~~~~~~~ 
 
-                if let Some((button_path, keys_added, keys_removed)) = canvas.click(mouse_pos.x, mouse_pos.y) {
-
-                    if let Err(e) = game.goto(&button_path) {
-                        eprintln!("could not go to slide {button_path:?}: {e}");
-                    } else {
-                        game.keys.extend(keys_added);
-                        for key in keys_removed {
-                            game.keys.remove(&key);
+                if settings_screen.open {
+                    let height = window.inner_size().height;
+                    match settings_screen.click(&mut settings, mouse_pos.y, height) {
+                        Some(SettingsAction::Changed) => {
+                            log::info!("settings changed: {settings:?}");
+                            ERRORS.lock().unwrap().banner_enabled = settings.error_banner;
+                            settings.save();
+                            apply_window_settings(&window, &settings);
+                            canvas.rebuild(&game, &settings);
                         }
-
-                        match serde_yaml::to_string(&SaveFile::from(&game)) {
-                            Ok(yaml) => {
-                                if let Err(e) = fs::write(SAVE_FILE_PATH, yaml) {
-                                    eprintln!("could not write save file: {e}")
+                        Some(SettingsAction::NewGame) => {
+                            match Game::try_from(SaveFile::default()) {
+                                Ok(new_game) => {
+                                    game = new_game;
+                                    logging::set_context(&game);
+                                    log::info!("started a new game");
+                                    write_save_file(&game);
+                                    canvas = Canvas::build(&game, &settings);
+                                    audio.set_music(&game.location, game.slide.music.as_ref());
                                 }
+                                Err(e) => report_error(format!("could not start a new game: {e}")),
                             }
-                            Err(e) => {eprintln!("could not serialize save file: {e}");}
                         }
-
-                        canvas = Canvas::build(&game);
-                        window.request_redraw();
+                        Some(SettingsAction::Close) | None => {}
+                    }
+                    window.request_redraw();
+                } else if transition.is_some() {
+                    skip_transition(&mut transition, &mut canvas, &window);
+                } else {
+                    let viewport = Viewport::new(settings.scaling, canvas.size, window.inner_size().width, window.inner_size().height);
+                    let Coords { x, y } = viewport.to_canvas(mouse_pos);
+                    if let Some(action) = canvas.click(x, y) {
+                        follow_button(&mut game, &mut canvas, &mut transition, &mut audio, &settings, &window, action);
                     }
-
                 }
 
                 // ~~~~~~~~~~~~~~~~ ^^^^ ~~~~~~~~~~~~~~~~ INPUT CODE ~~~~~~~~~~~~~~~~ ^^^^ ~~~~~~~~~~~~~~~~ 
 
             }
+            Event::WindowEvent { 
+                event: WindowEvent::KeyboardInput { 
+                    event: KeyEvent { 
+                        logical_key, 
+                        state: ElementState::Pressed, 
+                        repeat: false, 
+                        .. 
+                    },
+                    ..
+                },
+                window_id,
+            } if window_id == window.id() => {
+                if hotkey_matches(&settings.bindings.settings, &logical_key) {
+                    settings_screen.open = !settings_screen.open;
+                    window.request_redraw();
+                } else if debug_overlay_allowed && hotkey_matches(&settings.bindings.debug_overlay, &logical_key) {
+                    debug_overlay = !debug_overlay;
+                    window.request_redraw();
+                } else if hotkey_matches(&settings.bindings.fullscreen, &logical_key) {
+                    settings.fullscreen = !settings.fullscreen;
+                    settings.save();
+                    apply_window_settings(&window, &settings);
+                } else if settings_screen.open {
+                    // the game doesn't take input while the settings are open
+                } else if transition.is_some() {
+                    skip_transition(&mut transition, &mut canvas, &window);
+                } else if let Some(action) = canvas.press(&logical_key) {
+                    follow_button(&mut game, &mut canvas, &mut transition, &mut audio, &settings, &window, action);
+                }
+            }
             Event::WindowEvent { 
                 event: WindowEvent::CursorMoved { device_id: _, position },
                 window_id,
@@ -190,14 +483,96 @@ fn main() {
                 event: WindowEvent::CloseRequested,
                 window_id,
             } if window_id == window.id() => {
+                settings.save();
                 elwt.exit();
             }
             Event::WindowEvent {
-                event: WindowEvent::Resized(_),
+                event: WindowEvent::Resized(size),
                 window_id
             } if window_id == window.id() => {
+                if settings.scaling != Scaling::None && !settings.fullscreen {
+                    settings.window_size = Some(Coords { x: size.width, y: size.height });
+                }
                 window.request_redraw();
             }
+            Event::UserEvent(UserEvent::Console(command)) => {
+                log::info!("console: {command:?}");
+                let changed = match command {
+                    ConsoleCommand::Goto(path) => match game.goto(&path) {
+                        Ok(()) => {
+                            println!("now at {}", game.location.display());
+                            true
+                        }
+                        Err(e) => {
+                            println!("{e}");
+                            false
+                        }
+                    },
+                    ConsoleCommand::Give(key) => game.keys.insert(key),
+                    ConsoleCommand::Take(key) => {
+                        let removed = game.keys.remove(&key);
+                        if !removed {
+                            println!("the player doesn't have {key:?}");
+                        }
+                        removed
+                    }
+                    ConsoleCommand::Keys => {
+                        let mut keys: Vec<&str> = game.keys.iter().map(String::as_str).collect();
+                        keys.sort();
+                        println!("{}", keys.join(", "));
+                        false
+                    }
+                    ConsoleCommand::Reload => {
+                        IMAGE_CACHE.lock().unwrap().clear();
+                        match game.reload() {
+                            Ok(()) => true,
+                            Err(e) => {
+                                println!("{e}");
+                                false
+                            }
+                        }
+                    }
+                    ConsoleCommand::Screenshot(path) => {
+                        let path = path.unwrap_or_else(|| {
+                            let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+                            format!("screenshot-{timestamp}.png").into()
+                        });
+                        match console::save_screenshot(&canvas, &path) {
+                            Ok(()) => println!("saved {path:?}"),
+                            Err(e) => println!("could not save screenshot {path:?}: {e}"),
+                        }
+                        false
+                    }
+                };
+
+                if changed {
+                    logging::set_context(&game);
+                    write_save_file(&game);
+                    transition = None;
+                    canvas = Canvas::build(&game, &settings);
+                    filesystem::preload_reachable(&game.location, &game.slide);
+                    audio.set_music(&game.location, game.slide.music.as_ref());
+                    window.request_redraw();
+                }
+            }
+            Event::UserEvent(UserEvent::StoryChanged(changed)) => {
+                // the cache is keyed by paths as slides spell them, which may differ from the watcher's
+                let normalized: HashSet<PathBuf> = changed.iter().map(|path| filesystem::normalize_path(path)).collect();
+                IMAGE_CACHE.lock().unwrap().remove_matching(|path| normalized.contains(&filesystem::normalize_path(path)));
+
+                // keys are kept, so authors can keep testing from where they are
+                if watch::affects_current_slide(&game, &changed) {
+                    match game.reload() {
+                        Ok(()) => {
+                            log::info!("reloaded after changes to {changed:?}");
+                            canvas = Canvas::build(&game, &settings);
+                            audio.set_music(&game.location, game.slide.music.as_ref());
+                        }
+                        Err(e) => report_error(format!("could not reload slide: {e}")),
+                    }
+                    window.request_redraw();
+                }
+            }
             
             _ => {}
         }
@@ -206,3 +581,65 @@ fn main() {
 
 
 
+fn follow_button(
+    game: &mut Game, 
+    canvas: &mut Canvas, 
+    transition: &mut Option<TransitionState>,
+    audio: &mut Audio,
+    settings: &Settings,
+    window: &Window, 
+    action: Action,
+) {
+    if let Some(sound) = &action.sound {
+        audio.play_sound(&game.location, sound);
+    }
+
+    log::info!("going to {:?}", action.slide_path);
+    if let Err(e) = game.goto(&action.slide_path) {
+        report_error(format!("could not go to slide {:?}: {e}", action.slide_path));
+        window.request_redraw();
+        return
+    }
+
+    game.keys.extend(action.adds_keys);
+    for key in action.removes_keys {
+        game.keys.remove(&key);
+    }
+    logging::set_context(game);
+
+    write_save_file(game);
+
+    if let Some(new_transition) = action.transition.or(game.slide.transition) {
+        if !settings.reduced_motion {
+            log::debug!("starting {:?} transition", new_transition.kind);
+            *transition = Some(TransitionState::new(new_transition, canvas));
+        }
+    }
+    *canvas = Canvas::build(game, settings);
+    filesystem::preload_reachable(&game.location, &game.slide);
+    audio.set_music(&game.location, game.slide.music.as_ref());
+    window.request_redraw();
+}
+
+fn write_save_file(game: &Game) {
+    match serde_yaml::to_string(&SaveFile::from(game)) {
+        Ok(yaml) => {
+            if let Err(e) = fs::write(SAVE_FILE_PATH, yaml) {
+                report_error(format!("could not write save file: {e}"))
+            }
+        }
+        Err(e) => {report_error(format!("could not serialize save file: {e}"));}
+    }
+}
+
+fn apply_window_settings(window: &Window, settings: &Settings) {
+    window.set_fullscreen(settings.fullscreen.then_some(Fullscreen::Borderless(None)));
+    window.set_resizable(settings.scaling != Scaling::None);
+    window.request_redraw();
+}
+
+fn skip_transition(transition: &mut Option<TransitionState>, canvas: &mut Canvas, window: &Window) {
+    *transition = None;
+    canvas.mark_all_dirty();
+    window.request_redraw();
+}
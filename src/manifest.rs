@@ -0,0 +1,51 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{error::Error, filesystem::{prefix_path, read_yaml_file}, report::report_error};
+
+
+// Written by soweli Luna
+
+pub const MANIFEST_FILENAME: &str = "manifest.yaml";
+
+
+/// Details about the story as a whole, kept next to the root slide
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
+pub struct Manifest {
+    /// Shown as the window title
+    pub title: String,
+    pub author: Option<String>,
+}
+impl Default for Manifest {
+    fn default() -> Self {
+        Self {
+            title: "FerrousTale".into(),
+            author: None,
+        }
+    }
+}
+impl Manifest {
+    /// Reads the manifest, along with the fields that were ignored because they aren't known
+    pub fn read() -> Result<(Self, Vec<String>), Error> {
+        read_yaml_file(&prefix_path(Path::new(MANIFEST_FILENAME)))
+    }
+
+    /// Reads the manifest, which is optional, so only a broken one is reported
+    pub fn load() -> Self {
+        match Self::read() {
+            Ok((manifest, unknown_fields)) => {
+                for field in unknown_fields {
+                    log::warn!("ignoring unknown field {field:?} in {MANIFEST_FILENAME}");
+                }
+                manifest
+            }
+            Err(Error::Missing { .. }) => Self::default(),
+            Err(e) => {
+                report_error(&e);
+                Self::default()
+            }
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use crate::{
+    draw::{Button, Canvas, Hitbox, Viewport},
+    game::{Coords, Game},
+    text::{draw_line, draw_text, fill_rect, text_width, GLYPH_HEIGHT},
+};
+
+
+// Written by soweli Luna
+
+/// Clickables the player can use
+const AVAILABLE_COLOR: u32 = 0x40ff40;
+/// Clickables hidden because of `must_have_keys` or `mustnt_have_keys`
+const HIDDEN_COLOR: u32 = 0xff40ff;
+const PANEL_COLOR: u32 = 0x000000;
+
+
+/// Draws every hitbox with where it leads, along with the player's location and keys, over the window
+pub fn draw_debug_overlay(game: &Game, canvas: &Canvas, viewport: &Viewport, buffer: &mut [u32], width: u32, height: u32) {
+    let scale = (height / 320).max(1);
+    let line_height = (GLYPH_HEIGHT + 3) * scale;
+
+    for (buttons, color) in [(&canvas.hidden_buttons, HIDDEN_COLOR), (&canvas.buttons, AVAILABLE_COLOR)] {
+        for button in buttons {
+            let corners: Vec<Coords<i64>> = match &button.hitbox {
+                Hitbox::None => continue,
+                Hitbox::Rect { x1, y1, x2, y2 } => vec![
+                    Coords { x: *x1, y: *y1 },
+                    Coords { x: *x2 - 1, y: *y1 },
+                    Coords { x: *x2 - 1, y: *y2 - 1 },
+                    Coords { x: *x1, y: *y2 - 1 },
+                ],
+                Hitbox::Polygon(points) => points.clone(),
+            };
+            let corners: Vec<Coords<i64>> = corners.into_iter().map(|corner| viewport.to_window(corner)).collect();
+            for (index, a) in corners.iter().enumerate() {
+                let b = corners[(index + 1) % corners.len()];
+                draw_line(buffer, width, height, (a.x, a.y), (b.x, b.y), color);
+            }
+
+            let left = corners.iter().map(|corner| corner.x).min().unwrap_or(0);
+            let top = corners.iter().map(|corner| corner.y).min().unwrap_or(0);
+            let label = button_label(button);
+            fill_rect(buffer, width, height, left, top, text_width(&label, scale) + 2 * scale, line_height, PANEL_COLOR, 0.6);
+            draw_text(buffer, width, height, left + scale as i64, top + scale as i64, scale, &label, color);
+        }
+    }
+
+    let mut keys: Vec<&str> = game.keys.iter().map(String::as_str).collect();
+    keys.sort();
+    let mut lines = vec![
+        (format!("location: {}", game.location.display()), 0xffffff),
+        (format!("keys: {}", keys.join(", ")), 0xffffff),
+    ];
+    // clickables without a hitbox can't be outlined, so they're listed instead
+    for (buttons, color) in [(&canvas.buttons, AVAILABLE_COLOR), (&canvas.hidden_buttons, HIDDEN_COLOR)] {
+        for button in buttons.iter().filter(|button| matches!(button.hitbox, Hitbox::None)) {
+            lines.push((format!("hotkey only: {}", button_label(button)), color));
+        }
+    }
+
+    let panel_width = lines.iter().map(|(line, _)| text_width(line, scale)).max().unwrap_or(0) + 4 * scale;
+    let panel_height = line_height * lines.len() as u32 + 2 * scale;
+    let panel_top = height as i64 - panel_height as i64;
+    fill_rect(buffer, width, height, 0, panel_top, panel_width, panel_height, PANEL_COLOR, 0.6);
+    for (index, (line, color)) in lines.iter().enumerate() {
+        draw_text(
+            buffer, width, height,
+            (2 * scale) as i64,
+            panel_top + (2 * scale + index as u32 * line_height) as i64,
+            scale, line, *color,
+        );
+    }
+}
+
+fn button_label(button: &Button) -> String {
+    match &button.hotkey {
+        Some(hotkey) => format!("{} [{hotkey}]", button.slide_path.display()),
+        None => button.slide_path.display().to_string(),
+    }
+}
@@ -0,0 +1,236 @@
+use std::{collections::{BTreeMap, BTreeSet}, ffi::OsStr, fs, path::{Path, PathBuf}, sync::OnceLock};
+
+use image::{codecs::png::{CompressionType, FilterType, PngDecoder, PngEncoder}, DynamicImage, ImageEncoder};
+
+use crate::{
+    check::referenced_paths,
+    error::Error,
+    filesystem::{prefix_path, read_asset, slide_file, Slide, ROOT_PREFIX},
+    graph::StoryGraph,
+    manifest::MANIFEST_FILENAME,
+};
+
+
+// Written by soweli Luna
+
+/// Played without being asked for when it sits in the working directory and there's no story folder,
+/// so a story can be shipped as this file next to a stock executable
+pub const DEFAULT_PACK_PATH: &str = "story.ftpack";
+/// Starts every pack file, the last byte being the format version
+const MAGIC: &[u8; 8] = b"FTPACK\0\x01";
+
+/// The pack the story is read from, which takes priority over the story folder and the built in story
+pub static PACK: OnceLock<Pack> = OnceLock::new();
+
+
+/// A whole story in one file, keyed by the same paths `prefix_path` gives
+///
+/// The format is `MAGIC`, then the number of files as a little endian `u32`, then for each file
+/// the length of its path as a `u32`, the path as UTF-8, the length of its contents as a `u64` and the contents
+#[derive(Default)]
+pub struct Pack {
+    files: BTreeMap<PathBuf, Vec<u8>>,
+}
+impl Pack {
+    pub fn read(path: &Path) -> Result<Self, Error> {
+        let data = fs::read(path).map_err(|e| Error::Missing { path: path.into(), source: Some(e) })?;
+        Self::parse(&data).ok_or(Error::Decode { path: path.into(), message: "not a FerrousTale pack, or a damaged one".into() })
+    }
+
+    fn parse(mut data: &[u8]) -> Option<Self> {
+        if take(&mut data, MAGIC.len())? != MAGIC {
+            return None
+        }
+        let count = u32::from_le_bytes(take(&mut data, 4)?.try_into().ok()?);
+        let mut pack = Self::default();
+        for _ in 0..count {
+            let path_length = u32::from_le_bytes(take(&mut data, 4)?.try_into().ok()?);
+            let path = std::str::from_utf8(take(&mut data, path_length as usize)?).ok()?;
+            let length = u64::from_le_bytes(take(&mut data, 8)?.try_into().ok()?);
+            let contents = take(&mut data, usize::try_from(length).ok()?)?;
+            pack.files.insert(path.into(), contents.to_vec());
+        }
+        data.is_empty().then_some(pack)
+    }
+
+    fn write(&self, path: &Path) -> Result<(), Error> {
+        fs::write(path, self.to_bytes()).map_err(|e| Error::Write { path: path.into(), source: e })
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut data = MAGIC.to_vec();
+        data.extend((self.files.len() as u32).to_le_bytes());
+        for (file_path, contents) in &self.files {
+            // always forward slashes, so packs made on Windows load everywhere
+            let file_path = file_path.components().map(|component| component.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/");
+            data.extend((file_path.len() as u32).to_le_bytes());
+            data.extend(file_path.as_bytes());
+            data.extend((contents.len() as u64).to_le_bytes());
+            data.extend(contents);
+        }
+        data
+    }
+
+    pub fn file(&self, full_path: &Path) -> Option<&[u8]> {
+        self.files.get(full_path).map(Vec::as_slice)
+    }
+
+    pub fn is_dir(&self, full_path: &Path) -> bool {
+        self.files.keys().any(|path| path != full_path && path.starts_with(full_path))
+    }
+
+    /// Names of the files and folders directly inside `directory`
+    pub fn names_in(&self, directory: &Path) -> BTreeSet<&OsStr> {
+        self.files
+            .keys()
+            .filter_map(|path| path.strip_prefix(directory).ok()?.components().next())
+            .map(|component| component.as_os_str())
+            .collect()
+    }
+
+    /// Every file in the pack
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.files.keys().map(PathBuf::as_path)
+    }
+}
+
+impl FromIterator<(PathBuf, Vec<u8>)> for Pack {
+    fn from_iter<I: IntoIterator<Item = (PathBuf, Vec<u8>)>>(files: I) -> Self {
+        Self { files: files.into_iter().collect() }
+    }
+}
+
+fn take<'a>(data: &mut &'a [u8], length: usize) -> Option<&'a [u8]> {
+    if data.len() < length {
+        return None
+    }
+    let (taken, rest) = data.split_at(length);
+    *data = rest;
+    Some(taken)
+}
+
+
+/// Reads the story from `path`. Without one, `DEFAULT_PACK_PATH` is read if `find_default` is set
+/// and there's no story folder that should be played instead, so a stale pack never hides the story being worked on.
+pub fn load(path: Option<&Path>, find_default: bool) -> Result<(), Error> {
+    let story_folder = cfg!(feature="portable") && Path::new(ROOT_PREFIX).is_dir();
+    let path = match path {
+        Some(path) => path,
+        None if find_default && !story_folder && Path::new(DEFAULT_PACK_PATH).is_file() => Path::new(DEFAULT_PACK_PATH),
+        None => return Ok(()),
+    };
+    let pack = Pack::read(path)?;
+    log::info!("reading the story from {path:?}, {} files", pack.files.len());
+    let _ = PACK.set(pack);
+    Ok(())
+}
+
+
+/// What went into a pack
+pub struct PackSummary {
+    pub files: usize,
+    pub bytes: usize,
+    /// How much recompressing images saved
+    pub saved_bytes: usize,
+}
+
+/// Writes every file the slides reachable from `start` use into a pack at `output`, leaving out everything else
+pub fn pack_story(start: &Path, output: &Path, recompress: bool) -> Result<PackSummary, Error> {
+    let graph = StoryGraph::build(start.into());
+    let mut paths = BTreeSet::new();
+    for location in &graph.slides {
+        let slide = Slide::read_yaml(location)?;
+        paths.insert(slide_file(location));
+        paths.extend(referenced_paths(location, &slide));
+    }
+
+    let mut pack = Pack::default();
+    let manifest_path = prefix_path(Path::new(MANIFEST_FILENAME));
+    match read_asset(&manifest_path) {
+        Ok(manifest) => {
+            pack.files.insert(manifest_path, manifest);
+        }
+        Err(Error::Missing { .. }) => (),
+        Err(e) => return Err(e),
+    }
+
+    let mut saved_bytes = 0;
+    for path in paths {
+        let mut contents = read_asset(&path)?;
+        let is_png = path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("png"));
+        if recompress && is_png {
+            if let Some(smaller) = recompress_png(&contents) {
+                saved_bytes += contents.len() - smaller.len();
+                contents = smaller;
+            }
+        }
+        pack.files.insert(path, contents);
+    }
+
+    pack.write(output)?;
+    Ok(PackSummary {
+        files: pack.files.len(),
+        bytes: pack.files.values().map(Vec::len).sum(),
+        saved_bytes,
+    })
+}
+
+/// Encodes a PNG again as tightly as the encoder can, if that comes out smaller.
+/// Animated PNGs are left alone since re-encoding would keep only their first frame.
+fn recompress_png(data: &[u8]) -> Option<Vec<u8>> {
+    let decoder = PngDecoder::new(data).ok()?;
+    if decoder.is_apng() {
+        return None
+    }
+    let image = DynamicImage::from_decoder(decoder).ok()?;
+    let mut png = Vec::new();
+    PngEncoder::new_with_quality(&mut png, CompressionType::Best, FilterType::Adaptive)
+        .write_image(image.as_bytes(), image.width(), image.height(), image.color())
+        .ok()?;
+    (png.len() < data.len()).then_some(png)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example() -> Pack {
+        [
+            ("story/slide.yaml".into(), b"background_path: bg.png".to_vec()),
+            ("story/forest/bg.png".into(), vec![0, 1, 2, 255]),
+            ("story/empty".into(), Vec::new()),
+        ].into_iter().collect()
+    }
+
+    #[test]
+    fn round_trip() {
+        let pack = Pack::parse(&example().to_bytes()).unwrap();
+        assert_eq!(pack.files, example().files);
+        assert!(pack.is_dir(Path::new("story/forest")));
+        assert_eq!(pack.names_in(Path::new("story")).len(), 3);
+    }
+
+    #[test]
+    fn bad_magic_is_rejected() {
+        let mut data = example().to_bytes();
+        data[MAGIC.len() - 1] = 2;
+        assert!(Pack::parse(&data).is_none());
+        assert!(Pack::parse(b"").is_none());
+    }
+
+    #[test]
+    fn truncated_data_is_rejected() {
+        let data = example().to_bytes();
+        for length in [MAGIC.len() + 2, MAGIC.len() + 6, data.len() - 1] {
+            assert!(Pack::parse(&data[..length]).is_none(), "{length}");
+        }
+    }
+
+    #[test]
+    fn trailing_bytes_are_rejected() {
+        let mut data = example().to_bytes();
+        data.push(0);
+        assert!(Pack::parse(&data).is_none());
+    }
+}
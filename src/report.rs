@@ -0,0 +1,75 @@
+use std::{collections::VecDeque, fmt::Display, sync::{LazyLock, Mutex}, time::{Duration, Instant}};
+
+use crate::text::{draw_text, fill_rect, text_width, GLYPH_HEIGHT};
+
+
+// Written by soweli Luna
+
+/// How long an error stays on the banner
+const BANNER_TIME: Duration = Duration::from_secs(6);
+const BANNER_LINES: usize = 3;
+const BANNER_COLOR: u32 = 0x801818;
+
+pub static ERRORS: LazyLock<Mutex<ErrorReports>> = LazyLock::new(|| Mutex::new(ErrorReports::default()));
+
+
+/// Reports something the player should know went wrong, in the log and on the banner
+pub fn report_error(message: impl Display) {
+    let message = message.to_string();
+    log::error!("{message}");
+    ERRORS.lock().unwrap().push(message);
+}
+
+
+/// Recent errors, shown over the game when the banner is enabled
+#[derive(Default)]
+pub struct ErrorReports {
+    /// Off by default in release builds, see `Settings::error_banner`
+    pub banner_enabled: bool,
+    recent: VecDeque<(String, Instant)>,
+}
+impl ErrorReports {
+    fn push(&mut self, message: String) {
+        if !self.banner_enabled {
+            return
+        }
+        // the same missing asset tends to be reported over and over
+        self.recent.retain(|(recent, _)| *recent != message);
+        self.recent.push_back((message, Instant::now()));
+        while self.recent.len() > BANNER_LINES {
+            self.recent.pop_front();
+        }
+    }
+
+    /// Drops errors that have been shown long enough, returning whether any are left to show
+    pub fn update(&mut self) -> bool {
+        self.recent.retain(|(_, reported)| reported.elapsed() < BANNER_TIME);
+        !self.recent.is_empty()
+    }
+
+    /// When the oldest error should disappear
+    pub fn next_update(&self) -> Option<Instant> {
+        self.recent.front().map(|(_, reported)| *reported + BANNER_TIME)
+    }
+
+    /// Draws the banner along the top of the window
+    pub fn draw(&self, buffer: &mut [u32], width: u32, height: u32) {
+        if self.recent.is_empty() {
+            return
+        }
+        let scale = (height / 240).max(1);
+        let line_height = (GLYPH_HEIGHT + 4) * scale;
+        let max_chars = (width / text_width("m", scale).max(1)).saturating_sub(2) as usize;
+
+        fill_rect(buffer, width, height, 0, 0, width, line_height * self.recent.len() as u32 + 4 * scale, BANNER_COLOR, 0.9);
+        for (index, (message, _)) in self.recent.iter().enumerate() {
+            let line: String = message.chars().take(max_chars).collect();
+            draw_text(
+                buffer, width, height,
+                (4 * scale) as i64,
+                (4 * scale + index as u32 * line_height) as i64,
+                scale, &line, 0xffffff,
+            );
+        }
+    }
+}
@@ -0,0 +1,232 @@
+use std::{fs::{self, OpenOptions}, io::{self, Cursor, Write}, path::Path};
+
+use image::ImageOutputFormat;
+
+use crate::{
+    draw::{blend, to_rgb_image},
+    error::Error,
+    filesystem::{Slide, ROOT_PREFIX},
+    manifest::MANIFEST_FILENAME,
+    text::{draw_text, fill_rect, text_width, GLYPH_HEIGHT},
+};
+
+
+// Written by soweli Luna
+
+const BACKGROUND_WIDTH: u32 = 480;
+const BACKGROUND_HEIGHT: u32 = 270;
+const BUTTON_WIDTH: u32 = 128;
+const BUTTON_HEIGHT: u32 = 32;
+
+const SCHEMA_FILENAME: &str = "slide.schema.json";
+const VSCODE_SETTINGS: &str = r#"{
+    "yaml.schemas": {
+        "./slide.schema.json": "story/**/slide.yaml"
+    },
+    "yaml.customTags": ["!rect mapping", "!polygon mapping"]
+}
+"#;
+
+
+/// A slide of the starter story, with the images its YAML refers to
+struct StarterSlide {
+    /// Folder below the story root, `""` for the root slide
+    location: &'static str,
+    yaml: &'static str,
+    title: &'static str,
+    subtitle: &'static str,
+    /// Top and bottom colours of the background gradient
+    colors: (u32, u32),
+    buttons: &'static [(&'static str, &'static str)],
+}
+
+const STARTER_SLIDES: [StarterSlide; 4] = [
+    StarterSlide {
+        location: "",
+        yaml: "\
+# The first slide, every other slide is a folder below this one
+background_path: bg.png
+clickables:
+  - slide_path: forest
+    image_path: forest.png
+    hotkey: \"1\"
+    position: {x: 0.3, y: 0.75}
+    anchor: {x: 0.5, y: 0.5}
+  - slide_path: cave
+    image_path: cave.png
+    hotkey: \"2\"
+    position: {x: 0.7, y: 0.75}
+    anchor: {x: 0.5, y: 0.5}
+",
+        title: "A fork in the road",
+        subtitle: "Pick a path",
+        colors: (0x3a6ea5, 0xc0d8f0),
+        buttons: &[("forest.png", "Forest"), ("cave.png", "Cave")],
+    },
+    StarterSlide {
+        location: "forest",
+        yaml: "\
+background_path: bg.png
+clickables:
+  # keys remember what the player has done, the cave checks for this one
+  - slide_path: ..
+    image_path: back.png
+    hotkey: Backspace
+    adds_keys: [lantern]
+    position: {x: 0.5, y: 0.8}
+    anchor: {x: 0.5, y: 0.5}
+transition:
+  kind: crossfade
+",
+        title: "The forest",
+        subtitle: "You find an old lantern",
+        colors: (0x1f4d2b, 0x6fa86f),
+        buttons: &[("back.png", "Back")],
+    },
+    StarterSlide {
+        location: "cave",
+        yaml: "\
+background_path: bg.png
+clickables:
+  - slide_path: ..
+    image_path: back.png
+    hotkey: Backspace
+    position: {x: 0.3, y: 0.8}
+    anchor: {x: 0.5, y: 0.5}
+  # only shown once the player has the lantern from the forest
+  - slide_path: treasure
+    image_path: deeper.png
+    must_have_keys: [lantern]
+    position: {x: 0.7, y: 0.8}
+    anchor: {x: 0.5, y: 0.5}
+transition:
+  kind: fade_to_black
+",
+        title: "The cave",
+        subtitle: "Too dark to go on without a light",
+        colors: (0x1a1a22, 0x4a4a58),
+        buttons: &[("back.png", "Back"), ("deeper.png", "Go deeper")],
+    },
+    StarterSlide {
+        location: "cave/treasure",
+        yaml: "\
+background_path: bg.png
+clickables:
+  - slide_path: /
+    image_path: again.png
+    removes_keys: [lantern]
+    position: {x: 0.5, y: 0.8}
+    anchor: {x: 0.5, y: 0.5}
+",
+        title: "Treasure!",
+        subtitle: "The end",
+        colors: (0x8a6a10, 0xf0d060),
+        buttons: &[("again.png", "Play again")],
+    },
+];
+
+
+/// Creates a small playable story in `directory`, which must be new or empty, ready to run from there in portable mode
+pub fn new_story(directory: &Path, title: &str) -> Result<(), Error> {
+    // starting over someone's files, or mixing the starter story into them, is never what was meant
+    let is_empty = match fs::read_dir(directory) {
+        Ok(mut entries) => entries.next().is_none(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => true,
+        Err(e) => return Err(Error::Write { path: directory.into(), source: e }),
+    };
+    if !is_empty {
+        return Err(Error::Write {
+            path: directory.into(),
+            source: io::Error::new(io::ErrorKind::AlreadyExists, "the folder isn't empty"),
+        })
+    }
+    let story = directory.join(ROOT_PREFIX);
+
+    // editors with a YAML language server check slides against the schema as they're written
+    write(&directory.join(SCHEMA_FILENAME), Slide::json_schema().as_bytes())?;
+    write(&directory.join(".vscode/settings.json"), VSCODE_SETTINGS.as_bytes())?;
+    write(&story.join(MANIFEST_FILENAME), format!("title: {title:?}\n# author: your name\n").as_bytes())?;
+    for slide in &STARTER_SLIDES {
+        let slide_directory = story.join(slide.location);
+        write(&slide_directory.join("slide.yaml"), slide.yaml.as_bytes())?;
+        write(&slide_directory.join("bg.png"), &background(slide.title, slide.subtitle, slide.colors))?;
+        for (filename, label) in slide.buttons {
+            write(&slide_directory.join(filename), &button(label))?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes a new file, never replacing one that's already there
+fn write(path: &Path, contents: &[u8]) -> Result<(), Error> {
+    let write_error = |source| Error::Write { path: path.into(), source };
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(write_error)?;
+    }
+    OpenOptions::new().write(true).create_new(true).open(path).and_then(|mut file| file.write_all(contents)).map_err(write_error)
+}
+
+
+/// A vertical gradient with the slide's title, so each placeholder is easy to tell apart
+fn background(title: &str, subtitle: &str, (top, bottom): (u32, u32)) -> Vec<u8> {
+    let (width, height) = (BACKGROUND_WIDTH, BACKGROUND_HEIGHT);
+    let mut buffer = vec![0; (width * height) as usize];
+    for y in 0..height {
+        let color = blend(top, bottom, y as f32 / height as f32);
+        buffer[(y * width) as usize..((y + 1) * width) as usize].fill(color);
+    }
+
+    let title_scale = 3;
+    draw_text(
+        &mut buffer, width, height,
+        (width as i64 - text_width(title, title_scale) as i64) / 2,
+        (height / 4) as i64,
+        title_scale, title, 0xffffff,
+    );
+    draw_text(
+        &mut buffer, width, height,
+        (width as i64 - text_width(subtitle, 1) as i64) / 2,
+        (height / 4 + (GLYPH_HEIGHT + 6) * title_scale) as i64,
+        1, subtitle, 0xffffff,
+    );
+    encode_png(&buffer, width, height)
+}
+
+fn button(label: &str) -> Vec<u8> {
+    let (width, height) = (BUTTON_WIDTH, BUTTON_HEIGHT);
+    let mut buffer = vec![0xf0f0f0; (width * height) as usize];
+    fill_rect(&mut buffer, width, height, 2, 2, width - 4, height - 4, 0x303040, 1.0);
+    draw_text(
+        &mut buffer, width, height,
+        (width as i64 - text_width(label, 2) as i64) / 2,
+        (height as i64 - (GLYPH_HEIGHT * 2) as i64) / 2,
+        2, label, 0xf0f0f0,
+    );
+    encode_png(&buffer, width, height)
+}
+
+fn encode_png(buffer: &[u32], width: u32, height: u32) -> Vec<u8> {
+    let image = to_rgb_image(buffer, width, height);
+    let mut png = Cursor::new(Vec::new());
+    image.write_to(&mut png, ImageOutputFormat::Png).expect("encoding a PNG in memory");
+    png.into_inner()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refuses_a_folder_that_is_not_empty() {
+        let directory = std::env::temp_dir().join(format!("ferrous-tale-new-{}", std::process::id()));
+        _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("notes.txt"), "mine").unwrap();
+
+        assert!(matches!(new_story(&directory, "Test"), Err(Error::Write { .. })));
+        assert!(!directory.join(ROOT_PREFIX).exists());
+        assert_eq!(fs::read_to_string(directory.join("notes.txt")).unwrap(), "mine");
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
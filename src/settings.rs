@@ -0,0 +1,220 @@
+use std::fs;
+
+use serde::{Deserialize, Serialize};
+use serde_yaml as yaml;
+
+use crate::{game::Coords, report::report_error, text::{draw_text, fill_rect, text_width, GLYPH_HEIGHT}};
+
+
+// Written by soweli Luna
+
+/// Kept apart from the save file, so starting a new game never resets it
+const SETTINGS_FILE_PATH: &str = "settings.yaml";
+
+const TEXT_SPEEDS: [f32; 4] = [0.5, 1.0, 1.5, 2.0];
+
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Settings {
+    /// Only used when scaling, otherwise the window is the size of the slide
+    pub window_size: Option<Coords<u32>>,
+    pub fullscreen: bool,
+    pub scaling: Scaling,
+    /// How quickly timed elements appear, `2.0` being twice as fast
+    pub text_speed: f32,
+    /// Skips transitions, fade-ins and animations
+    pub reduced_motion: bool,
+    /// Shows errors over the game, on by default only in debug builds
+    pub error_banner: bool,
+    pub bindings: Bindings,
+}
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            window_size: None,
+            fullscreen: false,
+            scaling: Scaling::None,
+            text_speed: 1.0,
+            reduced_motion: false,
+            error_banner: cfg!(debug_assertions),
+            bindings: Bindings::default(),
+        }
+    }
+}
+impl Settings {
+    pub fn load() -> Self {
+        match &fs::read_to_string(SETTINGS_FILE_PATH) {
+            Ok(val) => {
+                match yaml::from_str(val) {
+                    Ok(val) => val,
+                    Err(e) => {
+                        report_error(format!("could not deserialize settings file: {e}"));
+                        Settings::default()
+                    }
+                }
+            }
+            Err(e) => {
+                log::info!("could not read settings file, using the defaults: {e}");
+                Settings::default()
+            }
+        }
+    }
+
+    pub fn save(&self) {
+        match yaml::to_string(self) {
+            Ok(yaml) => {
+                if let Err(e) = fs::write(SETTINGS_FILE_PATH, yaml) {
+                    report_error(format!("could not write settings file: {e}"))
+                }
+            }
+            Err(e) => {report_error(format!("could not serialize settings file: {e}"));}
+        }
+    }
+}
+
+
+/// Keys for engine actions, written like clickable hotkeys
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Bindings {
+    pub settings: String,
+    pub fullscreen: String,
+    /// Only works in debug builds or with `--dev`
+    pub debug_overlay: String,
+}
+impl Default for Bindings {
+    fn default() -> Self {
+        Self {
+            settings: "Escape".into(),
+            fullscreen: "F11".into(),
+            debug_overlay: "F3".into(),
+        }
+    }
+}
+
+
+/// How the slide is fitted to the window
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Scaling {
+    /// The window is resized to the slide
+    #[default]
+    None,
+    /// Scaled up by whole multiples, keeping pixel art crisp
+    Integer,
+    /// Scaled to fill as much of the window as possible
+    Fit,
+}
+impl Scaling {
+    fn next(self) -> Self {
+        match self {
+            Self::None => Self::Integer,
+            Self::Integer => Self::Fit,
+            Self::Fit => Self::None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Integer => "integer",
+            Self::Fit => "fit",
+        }
+    }
+}
+
+
+/// What the player picked on the settings screen
+pub enum SettingsAction {
+    Changed,
+    NewGame,
+    Close,
+}
+
+/// Menu drawn over the game, letting the player change settings without editing the file
+#[derive(Default)]
+pub struct SettingsScreen {
+    pub open: bool,
+}
+impl SettingsScreen {
+    const ROW_COUNT: u32 = 7;
+
+    fn rows(settings: &Settings) -> [String; Self::ROW_COUNT as usize] {
+        let on_off = |value: bool| if value { "on" } else { "off" };
+        [
+            format!("Fullscreen: {}", on_off(settings.fullscreen)),
+            format!("Scaling: {}", settings.scaling.name()),
+            format!("Text speed: {}x", settings.text_speed),
+            format!("Reduced motion: {}", on_off(settings.reduced_motion)),
+            format!("Error banner: {}", on_off(settings.error_banner)),
+            "New game".into(),
+            "Back".into(),
+        ]
+    }
+
+    /// Text scale, row height and where the first row starts
+    fn layout(height: u32) -> (u32, u32, u32) {
+        let scale = (height / 160).max(1);
+        let row_height = (GLYPH_HEIGHT + 6) * scale;
+        let first_row = (height / 2).saturating_sub(row_height * Self::ROW_COUNT / 2);
+        (scale, row_height, first_row)
+    }
+
+    pub fn draw(&self, settings: &Settings, buffer: &mut [u32], width: u32, height: u32) {
+        let (scale, row_height, first_row) = Self::layout(height);
+        fill_rect(buffer, width, height, 0, 0, width, height, 0x000000, 0.75);
+
+        let title = "Settings";
+        draw_text(
+            buffer, width, height,
+            (width as i64 - text_width(title, scale * 2) as i64) / 2,
+            first_row as i64 - (row_height * 2) as i64,
+            scale * 2, title, 0xffffff,
+        );
+
+        for (index, row) in Self::rows(settings).iter().enumerate() {
+            draw_text(
+                buffer, width, height,
+                (width as i64 - text_width(row, scale) as i64) / 2,
+                (first_row + index as u32 * row_height) as i64,
+                scale, row, 0xdddddd,
+            );
+        }
+
+        let hint = format!("{}: settings   {}: fullscreen", settings.bindings.settings, settings.bindings.fullscreen);
+        draw_text(
+            buffer, width, height,
+            (width as i64 - text_width(&hint, scale) as i64) / 2,
+            (first_row + (Self::ROW_COUNT + 1) * row_height) as i64,
+            scale, &hint, 0x888888,
+        );
+    }
+
+    /// Changes whichever setting is on the clicked row
+    pub fn click(&mut self, settings: &mut Settings, y: i64, height: u32) -> Option<SettingsAction> {
+        let (_, row_height, first_row) = Self::layout(height);
+        let row = (y - first_row as i64).div_euclid(row_height as i64);
+
+        match row {
+            0 => settings.fullscreen = !settings.fullscreen,
+            1 => settings.scaling = settings.scaling.next(),
+            2 => {
+                let current = TEXT_SPEEDS.iter().position(|speed| *speed >= settings.text_speed).unwrap_or(0);
+                settings.text_speed = TEXT_SPEEDS[(current + 1) % TEXT_SPEEDS.len()];
+            }
+            3 => settings.reduced_motion = !settings.reduced_motion,
+            4 => settings.error_banner = !settings.error_banner,
+            5 => {
+                self.open = false;
+                return Some(SettingsAction::NewGame)
+            }
+            6 => {
+                self.open = false;
+                return Some(SettingsAction::Close)
+            }
+            _ => return None,
+        }
+        Some(SettingsAction::Changed)
+    }
+}
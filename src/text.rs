@@ -0,0 +1,258 @@
+use crate::draw::blend;
+
+
+// Written by soweli Luna
+
+// Tiny built in bitmap font and shapes, so the engine can draw its own screens without any assets
+
+pub const GLYPH_WIDTH: u32 = 5;
+pub const GLYPH_HEIGHT: u32 = 7;
+/// Gap between characters, in unscaled pixels
+const GLYPH_SPACING: u32 = 1;
+
+
+/// How wide `text` is when drawn at `scale`
+pub fn text_width(text: &str, scale: u32) -> u32 {
+    text.chars().count() as u32 * (GLYPH_WIDTH + GLYPH_SPACING) * scale
+}
+
+/// Draws a single line of text with its top left corner at `x`, `y`, 
+/// clipped to the buffer. Characters outside printable ASCII are drawn as `?`.
+#[allow(clippy::too_many_arguments)]
+pub fn draw_text(buffer: &mut [u32], width: u32, height: u32, x: i64, y: i64, scale: u32, text: &str, color: u32) {
+    for (index, character) in text.chars().enumerate() {
+        let glyph = glyph(character);
+        let glyph_x = x + (index as u32 * (GLYPH_WIDTH + GLYPH_SPACING) * scale) as i64;
+        for (row, bits) in glyph.iter().enumerate() {
+            for column in 0..GLYPH_WIDTH {
+                if bits >> (GLYPH_WIDTH - 1 - column) & 1 == 1 {
+                    fill_rect(
+                        buffer, width, height, 
+                        glyph_x + (column * scale) as i64, 
+                        y + (row as u32 * scale) as i64, 
+                        scale, scale, color, 1.0,
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Blends a rectangle of `color` over the buffer, clipped to its edges
+#[allow(clippy::too_many_arguments)]
+pub fn fill_rect(buffer: &mut [u32], width: u32, height: u32, x: i64, y: i64, rect_width: u32, rect_height: u32, color: u32, opacity: f32) {
+    let x1 = x.clamp(0, width as i64) as u32;
+    let y1 = y.clamp(0, height as i64) as u32;
+    let x2 = (x + rect_width as i64).clamp(0, width as i64) as u32;
+    let y2 = (y + rect_height as i64).clamp(0, height as i64) as u32;
+    for row in y1..y2 {
+        for pixel in &mut buffer[(row * width + x1) as usize..(row * width + x2) as usize] {
+            *pixel = if opacity >= 1.0 { color } else { blend(*pixel, color, opacity) };
+        }
+    }
+}
+
+/// Draws a one pixel wide line between two points, clipped to the buffer
+pub fn draw_line(buffer: &mut [u32], width: u32, height: u32, from: (i64, i64), to: (i64, i64), color: u32) {
+    // stepping through far off points would take as long as the line is
+    let Some((from, to)) = clip_line(from, to, width, height) else { return };
+    let (mut x, mut y) = from;
+    let dx = (to.0 - x).abs();
+    let dy = -(to.1 - y).abs();
+    let step_x = if x < to.0 { 1 } else { -1 };
+    let step_y = if y < to.1 { 1 } else { -1 };
+    let mut error = dx + dy;
+    loop {
+        if (0..width as i64).contains(&x) && (0..height as i64).contains(&y) {
+            buffer[(y * width as i64 + x) as usize] = color;
+        }
+        if (x, y) == to {
+            break
+        }
+        let doubled = error * 2;
+        if doubled >= dy {
+            error += dy;
+            x += step_x;
+        }
+        if doubled <= dx {
+            error += dx;
+            y += step_y;
+        }
+    }
+}
+
+/// The part of a line that lies on a `width` by `height` buffer, if any, found with Liang–Barsky
+fn clip_line(from: (i64, i64), to: (i64, i64), width: u32, height: u32) -> Option<((i64, i64), (i64, i64))> {
+    if width == 0 || height == 0 {
+        return None
+    }
+    let (x, y) = (from.0 as f64, from.1 as f64);
+    let (dx, dy) = (to.0 as f64 - x, to.1 as f64 - y);
+    let (mut start, mut end) = (0.0_f64, 1.0_f64);
+    // each edge as how fast the line moves towards its outside, and how far inside it the line starts
+    for (towards_outside, distance_inside) in [
+        (-dx, x),
+        (dx, (width - 1) as f64 - x),
+        (-dy, y),
+        (dy, (height - 1) as f64 - y),
+    ] {
+        if towards_outside == 0.0 {
+            if distance_inside < 0.0 {
+                return None
+            }
+            continue
+        }
+        let crossing = distance_inside / towards_outside;
+        if towards_outside < 0.0 {
+            start = start.max(crossing);
+        } else {
+            end = end.min(crossing);
+        }
+    }
+    if start > end {
+        return None
+    }
+    let point = |t: f64| ((x + t * dx).round() as i64, (y + t * dy).round() as i64);
+    Some((point(start), point(end)))
+}
+
+fn glyph(character: char) -> &'static [u8; GLYPH_HEIGHT as usize] {
+    let index = match character {
+        ' '..='~' => character as usize - ' ' as usize,
+        _ => '?' as usize - ' ' as usize,
+    };
+    &GLYPHS[index]
+}
+
+/// Rows of each printable ASCII character from `' '` to `'~'`, most significant bit on the left
+const GLYPHS: [[u8; GLYPH_HEIGHT as usize]; 95] = [
+    [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000], // ' '
+    [0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00000, 0b00100], // '!'
+    [0b01010, 0b01010, 0b01010, 0b00000, 0b00000, 0b00000, 0b00000], // '"'
+    [0b01010, 0b01010, 0b11111, 0b01010, 0b11111, 0b01010, 0b01010], // '#'
+    [0b00100, 0b01111, 0b10100, 0b01110, 0b00101, 0b11110, 0b00100], // '$'
+    [0b11000, 0b11001, 0b00010, 0b00100, 0b01000, 0b10011, 0b00011], // '%'
+    [0b01100, 0b10010, 0b10100, 0b01000, 0b10101, 0b10010, 0b01101], // '&'
+    [0b00100, 0b00100, 0b01000, 0b00000, 0b00000, 0b00000, 0b00000], // '\''
+    [0b00010, 0b00100, 0b01000, 0b01000, 0b01000, 0b00100, 0b00010], // '('
+    [0b01000, 0b00100, 0b00010, 0b00010, 0b00010, 0b00100, 0b01000], // ')'
+    [0b00000, 0b00100, 0b10101, 0b01110, 0b10101, 0b00100, 0b00000], // '*'
+    [0b00000, 0b00100, 0b00100, 0b11111, 0b00100, 0b00100, 0b00000], // '+'
+    [0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b00100, 0b01000], // ','
+    [0b00000, 0b00000, 0b00000, 0b11111, 0b00000, 0b00000, 0b00000], // '-'
+    [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b01100], // '.'
+    [0b00000, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b00000], // '/'
+    [0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110], // '0'
+    [0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110], // '1'
+    [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111], // '2'
+    [0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110], // '3'
+    [0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010], // '4'
+    [0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110], // '5'
+    [0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110], // '6'
+    [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000], // '7'
+    [0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110], // '8'
+    [0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100], // '9'
+    [0b00000, 0b01100, 0b01100, 0b00000, 0b01100, 0b01100, 0b00000], // ':'
+    [0b00000, 0b01100, 0b01100, 0b00000, 0b01100, 0b00100, 0b01000], // ';'
+    [0b00010, 0b00100, 0b01000, 0b10000, 0b01000, 0b00100, 0b00010], // '<'
+    [0b00000, 0b00000, 0b11111, 0b00000, 0b11111, 0b00000, 0b00000], // '='
+    [0b01000, 0b00100, 0b00010, 0b00001, 0b00010, 0b00100, 0b01000], // '>'
+    [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b00000, 0b00100], // '?'
+    [0b01110, 0b10001, 0b00001, 0b01101, 0b10101, 0b10101, 0b01110], // '@'
+    [0b01110, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001], // 'A'
+    [0b11110, 0b10001, 0b10001, 0b11110, 0b10001, 0b10001, 0b11110], // 'B'
+    [0b01110, 0b10001, 0b10000, 0b10000, 0b10000, 0b10001, 0b01110], // 'C'
+    [0b11100, 0b10010, 0b10001, 0b10001, 0b10001, 0b10010, 0b11100], // 'D'
+    [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b11111], // 'E'
+    [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b10000], // 'F'
+    [0b01110, 0b10001, 0b10000, 0b10111, 0b10001, 0b10001, 0b01111], // 'G'
+    [0b10001, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001], // 'H'
+    [0b01110, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110], // 'I'
+    [0b00111, 0b00010, 0b00010, 0b00010, 0b00010, 0b10010, 0b01100], // 'J'
+    [0b10001, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010, 0b10001], // 'K'
+    [0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b11111], // 'L'
+    [0b10001, 0b11011, 0b10101, 0b10101, 0b10001, 0b10001, 0b10001], // 'M'
+    [0b10001, 0b10001, 0b11001, 0b10101, 0b10011, 0b10001, 0b10001], // 'N'
+    [0b01110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110], // 'O'
+    [0b11110, 0b10001, 0b10001, 0b11110, 0b10000, 0b10000, 0b10000], // 'P'
+    [0b01110, 0b10001, 0b10001, 0b10001, 0b10101, 0b10010, 0b01101], // 'Q'
+    [0b11110, 0b10001, 0b10001, 0b11110, 0b10100, 0b10010, 0b10001], // 'R'
+    [0b01111, 0b10000, 0b10000, 0b01110, 0b00001, 0b00001, 0b11110], // 'S'
+    [0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100], // 'T'
+    [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110], // 'U'
+    [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100], // 'V'
+    [0b10001, 0b10001, 0b10001, 0b10101, 0b10101, 0b10101, 0b01010], // 'W'
+    [0b10001, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001, 0b10001], // 'X'
+    [0b10001, 0b10001, 0b01010, 0b00100, 0b00100, 0b00100, 0b00100], // 'Y'
+    [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b11111], // 'Z'
+    [0b01110, 0b01000, 0b01000, 0b01000, 0b01000, 0b01000, 0b01110], // '['
+    [0b00000, 0b10000, 0b01000, 0b00100, 0b00010, 0b00001, 0b00000], // '\\'
+    [0b01110, 0b00010, 0b00010, 0b00010, 0b00010, 0b00010, 0b01110], // ']'
+    [0b00100, 0b01010, 0b10001, 0b00000, 0b00000, 0b00000, 0b00000], // '^'
+    [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b11111], // '_'
+    [0b01000, 0b00100, 0b00010, 0b00000, 0b00000, 0b00000, 0b00000], // '`'
+    [0b00000, 0b00000, 0b01110, 0b00001, 0b01111, 0b10001, 0b01111], // 'a'
+    [0b10000, 0b10000, 0b10110, 0b11001, 0b10001, 0b10001, 0b11110], // 'b'
+    [0b00000, 0b00000, 0b01110, 0b10000, 0b10000, 0b10001, 0b01110], // 'c'
+    [0b00001, 0b00001, 0b01101, 0b10011, 0b10001, 0b10001, 0b01111], // 'd'
+    [0b00000, 0b00000, 0b01110, 0b10001, 0b11111, 0b10000, 0b01110], // 'e'
+    [0b00110, 0b01001, 0b01000, 0b11100, 0b01000, 0b01000, 0b01000], // 'f'
+    [0b00000, 0b01111, 0b10001, 0b10001, 0b01111, 0b00001, 0b01110], // 'g'
+    [0b10000, 0b10000, 0b10110, 0b11001, 0b10001, 0b10001, 0b10001], // 'h'
+    [0b00100, 0b00000, 0b01100, 0b00100, 0b00100, 0b00100, 0b01110], // 'i'
+    [0b00010, 0b00000, 0b00110, 0b00010, 0b00010, 0b10010, 0b01100], // 'j'
+    [0b10000, 0b10000, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010], // 'k'
+    [0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110], // 'l'
+    [0b00000, 0b00000, 0b11010, 0b10101, 0b10101, 0b10001, 0b10001], // 'm'
+    [0b00000, 0b00000, 0b10110, 0b11001, 0b10001, 0b10001, 0b10001], // 'n'
+    [0b00000, 0b00000, 0b01110, 0b10001, 0b10001, 0b10001, 0b01110], // 'o'
+    [0b00000, 0b00000, 0b11110, 0b10001, 0b11110, 0b10000, 0b10000], // 'p'
+    [0b00000, 0b00000, 0b01101, 0b10011, 0b01111, 0b00001, 0b00001], // 'q'
+    [0b00000, 0b00000, 0b10110, 0b11001, 0b10000, 0b10000, 0b10000], // 'r'
+    [0b00000, 0b00000, 0b01110, 0b10000, 0b01110, 0b00001, 0b11110], // 's'
+    [0b01000, 0b01000, 0b11100, 0b01000, 0b01000, 0b01001, 0b00110], // 't'
+    [0b00000, 0b00000, 0b10001, 0b10001, 0b10001, 0b10011, 0b01101], // 'u'
+    [0b00000, 0b00000, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100], // 'v'
+    [0b00000, 0b00000, 0b10001, 0b10001, 0b10101, 0b10101, 0b01010], // 'w'
+    [0b00000, 0b00000, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001], // 'x'
+    [0b00000, 0b00000, 0b10001, 0b10001, 0b01111, 0b00001, 0b01110], // 'y'
+    [0b00000, 0b00000, 0b11111, 0b00010, 0b00100, 0b01000, 0b11111], // 'z'
+    [0b00010, 0b00100, 0b00100, 0b01000, 0b00100, 0b00100, 0b00010], // '{'
+    [0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100], // '|'
+    [0b01000, 0b00100, 0b00100, 0b00010, 0b00100, 0b00100, 0b01000], // '}'
+    [0b00000, 0b00000, 0b01000, 0b10101, 0b00010, 0b00000, 0b00000], // '~'
+];
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lit(buffer: &[u32]) -> usize {
+        buffer.iter().filter(|pixel| **pixel != 0).count()
+    }
+
+    #[test]
+    fn far_off_line_is_clipped_to_the_buffer() {
+        let mut buffer = vec![0; 100];
+        draw_line(&mut buffer, 10, 10, (-1_000_000_000, -1_000_000_000), (1_000_000_000, 1_000_000_000), 1);
+        assert_eq!(lit(&buffer), 10);
+        assert_eq!((buffer[0], buffer[99]), (1, 1));
+    }
+
+    #[test]
+    fn line_outside_the_buffer_draws_nothing() {
+        let mut buffer = vec![0; 100];
+        draw_line(&mut buffer, 10, 10, (-5, -5), (20, -1), 1);
+        draw_line(&mut buffer, 10, 10, (10, 0), (10, 9), 1);
+        assert_eq!(lit(&buffer), 0);
+    }
+
+    #[test]
+    fn line_inside_the_buffer_is_unchanged() {
+        let mut buffer = vec![0; 100];
+        draw_line(&mut buffer, 10, 10, (2, 3), (7, 3), 1);
+        assert_eq!(lit(&buffer), 6);
+        assert!((2..=7).all(|x| buffer[30 + x] == 1));
+    }
+}
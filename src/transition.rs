@@ -0,0 +1,202 @@
+use std::time::{Duration, Instant};
+
+use crate::{draw::{blend, Canvas}, game::{Direction, Transition, TransitionKind}};
+
+
+// Written by soweli Luna
+
+/// A transition in progress, blending a snapshot of the previous canvas into the current one
+pub struct TransitionState {
+    transition: Transition,
+    from: Vec<u32>,
+    from_width: u32,
+    from_height: u32,
+    start: Instant,
+}
+impl TransitionState {
+    pub fn new(transition: Transition, from: &Canvas) -> Self {
+        Self {
+            transition,
+            from: from.framebuffer().to_vec(),
+            from_width: from.size.x as u32,
+            from_height: from.size.y as u32,
+            start: Instant::now(),
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.start.elapsed() >= Duration::from_millis(self.transition.duration_ms)
+    }
+
+    /// How far along the transition is `elapsed` after it started, from `0.0` to `1.0`
+    fn progress_at(&self, elapsed: Duration) -> f32 {
+        if self.transition.duration_ms == 0 {
+            return 1.0
+        }
+        (elapsed.as_secs_f32() * 1000.0 / self.transition.duration_ms as f32).min(1.0)
+    }
+
+    /// Draws the current point in the transition into a frame the size of the new canvas
+    pub fn draw(&self, to: &Canvas, frame: &mut Vec<u32>) {
+        let t = self.progress_at(self.start.elapsed());
+        self.draw_at(t, to.framebuffer(), to.size.x as u32, to.size.y as u32, frame);
+    }
+
+    /// Draws the transition at progress `t` into a frame the size of the new framebuffer
+    fn draw_at(&self, t: f32, to: &[u32], width: u32, height: u32, frame: &mut Vec<u32>) {
+        frame.resize((width * height) as usize, 0);
+        let from = |x: u32, y: u32| sample(&self.from, self.from_width, self.from_height, x, y);
+        let to = |x: u32, y: u32| sample(to, width, height, x, y);
+
+        for y in 0..height {
+            for x in 0..width {
+                frame[(y * width + x) as usize] = match self.transition.kind {
+                    TransitionKind::FadeToBlack => {
+                        if t < 0.5 {
+                            blend(from(x, y), 0, t * 2.0)
+                        } else {
+                            blend(0, to(x, y), t * 2.0 - 1.0)
+                        }
+                    }
+                    TransitionKind::Crossfade => blend(from(x, y), to(x, y), t),
+                    TransitionKind::Dissolve => {
+                        if noise(x, y) < t { to(x, y) } else { from(x, y) }
+                    }
+                    TransitionKind::Wipe => {
+                        let revealed = match self.transition.direction {
+                            Direction::Left => x as f32 >= width as f32 * (1.0 - t),
+                            Direction::Right => (x as f32) < width as f32 * t,
+                            Direction::Up => y as f32 >= height as f32 * (1.0 - t),
+                            Direction::Down => (y as f32) < height as f32 * t,
+                        };
+                        if revealed { to(x, y) } else { from(x, y) }
+                    }
+                    TransitionKind::Slide => {
+                        // the new slide pushes the old one out in the given direction
+                        let shift_x = (width as f32 * t) as u32;
+                        let shift_y = (height as f32 * t) as u32;
+                        match self.transition.direction {
+                            Direction::Left => if x < width - shift_x { from(x + shift_x, y) } else { to(x + shift_x - width, y) },
+                            Direction::Right => if x >= shift_x { from(x - shift_x, y) } else { to(x + width - shift_x, y) },
+                            Direction::Up => if y < height - shift_y { from(x, y + shift_y) } else { to(x, y + shift_y - height) },
+                            Direction::Down => if y >= shift_y { from(x, y - shift_y) } else { to(x, y + height - shift_y) },
+                        }
+                    }
+                };
+            }
+        }
+    }
+}
+
+
+fn sample(framebuffer: &[u32], width: u32, height: u32, x: u32, y: u32) -> u32 {
+    if x < width && y < height {
+        framebuffer[(y * width + x) as usize]
+    } else {
+        0
+    }
+}
+
+/// Cheap per pixel hash in `0.0..1.0`, so dissolves look the same every time
+fn noise(x: u32, y: u32) -> f32 {
+    let mut hash = x.wrapping_mul(0x9e37_79b1) ^ y.wrapping_mul(0x85eb_ca77);
+    hash ^= hash >> 15;
+    hash = hash.wrapping_mul(0x2c1b_3c6d);
+    hash ^= hash >> 12;
+    (hash & 0xffff) as f32 / 65536.0
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KINDS: [TransitionKind; 5] = [
+        TransitionKind::FadeToBlack,
+        TransitionKind::Crossfade,
+        TransitionKind::Wipe,
+        TransitionKind::Slide,
+        TransitionKind::Dissolve,
+    ];
+    const DIRECTIONS: [Direction; 4] = [Direction::Left, Direction::Right, Direction::Up, Direction::Down];
+
+    /// A framebuffer where every pixel is different, offset by `base`
+    fn pattern(width: u32, height: u32, base: u32) -> Vec<u32> {
+        (0..width * height).map(|index| base + index).collect()
+    }
+
+    fn state(kind: TransitionKind, direction: Direction, duration_ms: u64, from_width: u32, from_height: u32) -> TransitionState {
+        TransitionState {
+            transition: Transition { kind, direction, duration_ms },
+            from: pattern(from_width, from_height, 0x10),
+            from_width,
+            from_height,
+            start: Instant::now(),
+        }
+    }
+
+    fn every_transition() -> impl Iterator<Item = (TransitionKind, Direction)> {
+        KINDS.into_iter().flat_map(|kind| DIRECTIONS.into_iter().map(move |direction| (kind, direction)))
+    }
+
+    #[test]
+    fn starts_on_the_old_slide_and_ends_on_the_new_one() {
+        let to = pattern(8, 6, 0x100);
+        let mut frame = Vec::new();
+        for (kind, direction) in every_transition() {
+            let state = state(kind, direction, 500, 8, 6);
+            state.draw_at(0.0, &to, 8, 6, &mut frame);
+            assert_eq!(frame, state.from, "{kind:?} {direction:?} at t=0");
+            state.draw_at(1.0, &to, 8, 6, &mut frame);
+            assert_eq!(frame, to, "{kind:?} {direction:?} at t=1");
+        }
+    }
+
+    #[test]
+    fn zero_duration_transitions_are_over_at_once() {
+        let to = pattern(8, 6, 0x100);
+        let mut frame = Vec::new();
+        for (kind, direction) in every_transition() {
+            let state = state(kind, direction, 0, 8, 6);
+            assert_eq!(state.progress_at(Duration::ZERO), 1.0);
+            assert!(state.is_finished());
+            state.draw_at(state.progress_at(Duration::ZERO), &to, 8, 6, &mut frame);
+            assert_eq!(frame, to, "{kind:?} {direction:?}");
+        }
+    }
+
+    #[test]
+    fn progress_follows_the_duration() {
+        let state = state(TransitionKind::Crossfade, Direction::Left, 400, 1, 1);
+        assert_eq!(state.progress_at(Duration::ZERO), 0.0);
+        assert_eq!(state.progress_at(Duration::from_millis(100)), 0.25);
+        assert_eq!(state.progress_at(Duration::from_secs(10)), 1.0);
+    }
+
+    #[test]
+    fn slide_halfway_shows_both_slides_side_by_side() {
+        let to = pattern(4, 1, 0x100);
+        let mut frame = Vec::new();
+        state(TransitionKind::Slide, Direction::Left, 500, 4, 1).draw_at(0.5, &to, 4, 1, &mut frame);
+        assert_eq!(frame, [0x12, 0x13, 0x100, 0x101]);
+        state(TransitionKind::Slide, Direction::Right, 500, 4, 1).draw_at(0.5, &to, 4, 1, &mut frame);
+        assert_eq!(frame, [0x102, 0x103, 0x10, 0x11]);
+    }
+
+    #[test]
+    fn canvases_of_different_sizes_fill_the_new_frame() {
+        let mut frame = Vec::new();
+        for (to_width, to_height) in [(6, 4), (3, 2)] {
+            let to = pattern(to_width, to_height, 0x100);
+            for (kind, direction) in every_transition() {
+                let state = state(kind, direction, 500, 4, 3);
+                for t in [0.0, 0.3, 0.5, 1.0] {
+                    state.draw_at(t, &to, to_width, to_height, &mut frame);
+                    assert_eq!(frame.len(), (to_width * to_height) as usize);
+                }
+                state.draw_at(1.0, &to, to_width, to_height, &mut frame);
+                assert_eq!(frame, to, "{kind:?} {direction:?}");
+            }
+        }
+    }
+}
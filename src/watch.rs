@@ -0,0 +1,70 @@
+use std::{collections::{HashMap, HashSet}, fs, path::{Path, PathBuf}, thread, time::{Duration, SystemTime}};
+
+use winit::event_loop::EventLoopProxy;
+
+use crate::{filesystem::{canonical_join, normalize_path, prefix_path, slide_file, ROOT_PREFIX}, game::Game, UserEvent};
+
+
+// Written by soweli Luna
+
+/// How often the story folder is scanned, quick enough to feel live without spinning the disk
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+
+/// Scans the story folder on a background thread, sending `UserEvent::StoryChanged`
+/// with every file that was added, removed or modified since the last scan
+pub fn watch_story(proxy: EventLoopProxy<UserEvent>) {
+    thread::spawn(move || {
+        let root = prefix_path(Path::new("/"));
+        let mut previous = scan(&root);
+        loop {
+            thread::sleep(POLL_INTERVAL);
+            let current = scan(&root);
+
+            let mut changed: Vec<PathBuf> = current
+                .iter()
+                .filter(|(path, modified)| previous.get(*path) != Some(modified))
+                .map(|(path, _)| path.clone())
+                .collect();
+            changed.extend(previous.keys().filter(|path| !current.contains_key(*path)).cloned());
+
+            if !changed.is_empty() {
+                log::debug!("story files changed: {changed:?}");
+                if proxy.send_event(UserEvent::StoryChanged(changed)).is_err() {
+                    // the event loop is gone, so the game is closing
+                    return
+                }
+            }
+            previous = current;
+        }
+    });
+    log::info!("watching {ROOT_PREFIX:?} for changes");
+}
+
+/// When every file under `directory` was last modified
+fn scan(directory: &Path) -> HashMap<PathBuf, SystemTime> {
+    let mut files = HashMap::new();
+    let mut to_visit = vec![directory.to_path_buf()];
+    while let Some(directory) = to_visit.pop() {
+        let Ok(entries) = fs::read_dir(&directory) else { continue };
+        for entry in entries.flatten() {
+            let Ok(metadata) = entry.metadata() else { continue };
+            if metadata.is_dir() {
+                to_visit.push(entry.path());
+            } else if let Ok(modified) = metadata.modified() {
+                files.insert(entry.path(), modified);
+            }
+        }
+    }
+    files
+}
+
+/// Whether any of the changed files is the current slide or something it shows or plays
+pub fn affects_current_slide(game: &Game, changed: &[PathBuf]) -> bool {
+    let asset = |path: &Path| prefix_path(&canonical_join(&game.location, path));
+    let mut dependencies = vec![slide_file(&game.location)];
+    dependencies.extend(game.slide.images().into_iter().map(|(image_path, _)| asset(image_path)));
+    dependencies.extend(game.slide.music.as_deref().map(asset));
+    let dependencies: HashSet<PathBuf> = dependencies.iter().map(|path| normalize_path(path)).collect();
+    changed.iter().any(|path| dependencies.contains(&normalize_path(path)))
+}